@@ -1,9 +1,50 @@
-mod domain;
+pub mod domain;
+pub mod html;
 mod input;
+pub mod layout;
+pub mod options;
+pub mod stats;
+pub mod svg;
+
+pub use options::Options;
+
+pub fn get_map(input: &str, options: &Options) -> Result<domain::Map, String> {
+    get_map_and_layout(input, options).map(|(map, _)| map)
+}
+
+/// Also returns the node positions, to seed the layout of a later edit
+/// through `Options::previous_layout`.
+pub fn get_map_and_layout(
+    input: &str,
+    options: &Options,
+) -> Result<(domain::Map, layout::LayoutCache), String> {
+    input::read_input_with_options(input, options)
+}
+
+pub fn render_graph<R: domain::Renderer>(
+    input: &str,
+    options: &Options,
+    renderer: R,
+) -> Result<R::Output, String> {
+    let map = get_map(input, options)?;
+    Ok(map.render(renderer))
+}
+
+pub fn get_cells(input: &str, options: &Options) -> Result<domain::Canvas<domain::Cell>, String> {
+    let map = get_map(input, options)?;
+    Ok(map.get_cells())
+}
 
 pub fn get_graph(input: &str) -> Result<String, String> {
-    let map = input::read_input(input)?;
-    Ok(map.get_picture())
+    render_graph(input, &Options::default(), domain::AsciiRenderer::new())
+}
+
+pub fn get_svg_graph(input: &str) -> Result<String, String> {
+    render_graph(input, &Options::default(), svg::SvgRenderer::new())
+}
+
+pub fn get_html_graph(input: &str) -> Result<String, String> {
+    render_graph(input, &Options::default(), html::HtmlRenderer::new())
 }
 
 #[cfg(test)]
@@ -54,10 +95,10 @@ C -> \"This is our test\"";
 +------------------+    +---+
 | This is our test |<---| C |
 +------------------+    +---+
-  |                       ^  
-  |                       |  
-  |         +---+         |  
-  --------->| B |---------|  
+          |               ^  
+          |               |  
+          | +---+         |  
+          ->| B |---------|  
             +---+            
 ";
         let output = get_graph(input);
@@ -67,4 +108,245 @@ C -> \"This is our test\"";
             assert_eq!(expected, ok_output);
         }
     }
+
+    #[test]
+    fn get_graph_is_deterministic() {
+        let input = "\
+A -> B
+A -> C
+A -> D
+B -> D
+C -> D
+D -> A
+E -> F";
+        let first = (
+            get_graph(input),
+            get_svg_graph(input),
+            get_html_graph(input),
+        );
+        for _ in 0..10 {
+            let next = (
+                get_graph(input),
+                get_svg_graph(input),
+                get_html_graph(input),
+            );
+            assert_eq!(first, next);
+        }
+    }
+
+    #[test]
+    fn get_cells_with_provenance() {
+        let input = "\
+A -> B
+B -> C
+C -> A";
+        let cells = get_cells(input, &Options::default()).unwrap();
+        let picture = get_graph(input).unwrap();
+
+        let glyphs = (0..cells.get_height())
+            .map(|y| {
+                let mut row = cells
+                    .get_row(y)
+                    .iter()
+                    .map(|cell| cell.glyph)
+                    .collect::<String>();
+                row.push('\n');
+                row
+            })
+            .collect::<String>();
+        assert_eq!(picture, glyphs);
+
+        let text = cells.get(&domain::Point { x: 2, y: 1 }).unwrap();
+        assert_eq!('A', text.glyph);
+        assert_eq!(domain::CellKind::NodeText, text.kind);
+        assert_eq!(
+            Some(domain::CellOwner::Node {
+                name: "A".to_owned()
+            }),
+            text.owner
+        );
+        assert_eq!(Some(1), text.line);
+
+        let border = cells.get(&domain::Point { x: 0, y: 0 }).unwrap();
+        assert_eq!(domain::CellKind::NodeBorder, border.kind);
+
+        let head = cells.get(&domain::Point { x: 5, y: 1 }).unwrap();
+        assert_eq!('<', head.glyph);
+        assert_eq!(domain::CellKind::ArrowHead, head.kind);
+        assert_eq!(
+            Some(domain::CellOwner::Edge {
+                from: "C".to_owned(),
+                to: "A".to_owned()
+            }),
+            head.owner
+        );
+        assert_eq!(Some(3), head.line);
+
+        let body = cells.get(&domain::Point { x: 2, y: 4 }).unwrap();
+        assert_eq!(domain::CellKind::ArrowBody, body.kind);
+        assert_eq!(Some(1), body.line);
+
+        let empty = cells.get(&domain::Point { x: 10, y: 3 }).unwrap();
+        assert_eq!(domain::CellKind::Empty, empty.kind);
+        assert_eq!(None, empty.owner);
+    }
+
+    #[test]
+    fn get_graph_with_direction_directive() {
+        let input = "\
+direction LR
+A -> B
+A -> C";
+        let expected = "                        +---+
+              --------->| B |
++---+         |         +---+
+|   |---------|              
+| A |---------|              
++---+         |         +---+
+              --------->| C |
+                        +---+
+";
+        let options = Options {
+            layout: Box::new(layout::LayeredLayout),
+            ..Options::default()
+        };
+        let output = render_graph(input, &options, domain::AsciiRenderer::new());
+        assert_eq!(Ok(expected.to_owned()), output);
+    }
+
+    #[test]
+    fn get_graph_with_layered_layout_draws_top_to_bottom_by_default() {
+        let input = "Hub -> A\nHub -> B\nHub -> C\nG -> Hub\nH -> Hub";
+        let get_output = |direction| {
+            let options = Options {
+                layout: Box::new(layout::LayeredLayout),
+                direction,
+                ..Options::default()
+            };
+            render_graph(input, &options, domain::AsciiRenderer::new())
+        };
+        assert_eq!(
+            get_output(None),
+            get_output(Some(layout::LayoutDirection::TopToBottom))
+        );
+    }
+
+    #[test]
+    fn get_graph_with_direction_option_overriding_directive() {
+        let input = "\
+direction LR
+A -> B";
+        let expected = "\
++---+
+| A |
++---+
+  |  
+  v  
++---+
+| B |
++---+
+";
+        let options = Options {
+            layout: Box::new(layout::LayeredLayout),
+            direction: Some(layout::LayoutDirection::TopToBottom),
+            ..Options::default()
+        };
+        let output = render_graph(input, &options, domain::AsciiRenderer::new());
+        assert_eq!(Ok(expected.to_owned()), output);
+    }
+
+    struct RowLayout;
+    impl layout::LayoutEngine for RowLayout {
+        fn layout(&self, input: &layout::LayoutInput) -> Vec<layout::NodeApproximation> {
+            input
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(index, name)| layout::NodeApproximation {
+                    name: name.to_owned(),
+                    position: layout::PointApproximation { x: index * 3, y: 0 },
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn get_map_keeps_nodes_of_previous_layout() {
+        let input = "A -> B\nB -> C\nC -> D\nD -> A";
+        let (_, previous_layout) = get_map_and_layout(input, &Options::default()).unwrap();
+        let options = Options {
+            previous_layout: Some(previous_layout.clone()),
+            ..Options::default()
+        };
+        let (_, layout) = get_map_and_layout(&format!("{}\nD -> E", input), &options).unwrap();
+
+        for node in ["A", "B", "C", "D"] {
+            assert_eq!(layout.get(node), previous_layout.get(node));
+        }
+        assert!(layout.get("E").is_some());
+    }
+
+    #[test]
+    fn get_graph_with_custom_layout() {
+        let input = "\
+A -> B
+B -> C";
+        let expected = "\
++---+       +---+       +---+
+| A |------>| B |------>| C |
++---+       +---+       +---+
+";
+        let options = Options {
+            layout: Box::new(RowLayout),
+            ..Options::default()
+        };
+        let output = render_graph(input, &options, domain::AsciiRenderer::new());
+        assert_eq!(Ok(expected.to_owned()), output);
+    }
+
+    #[test]
+    fn get_graph_with_engine_by_name() {
+        assert!(layout::get_engine("force-directed").is_ok());
+        assert!(layout::get_engine("layered").is_ok());
+        assert_eq!(
+            Some("Unknown layout: circular".to_owned()),
+            layout::get_engine("circular").err()
+        );
+    }
+
+    struct CountingRenderer {
+        nodes: usize,
+        arrows: usize,
+    }
+    impl domain::Renderer for CountingRenderer {
+        type Output = (usize, usize);
+
+        fn begin(&mut self, _width: usize, _height: usize) {}
+        fn add_arrow(&mut self, _arrow: &domain::Arrow) {
+            self.arrows += 1;
+        }
+        fn add_node(&mut self, _anchor: &domain::Point, _node: &domain::Node) {
+            self.nodes += 1;
+        }
+        fn finish(self) -> (usize, usize) {
+            (self.nodes, self.arrows)
+        }
+    }
+
+    #[test]
+    fn render_with_custom_renderer() {
+        let input = "\
+A -> B
+B -> C
+C -> A";
+        let output = render_graph(
+            input,
+            &Options::default(),
+            CountingRenderer {
+                nodes: 0,
+                arrows: 0,
+            },
+        );
+        assert_eq!(Ok((3, 3)), output);
+    }
 }
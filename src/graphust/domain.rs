@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 #[derive(Debug, PartialEq)]
 pub enum BorderType {
@@ -26,7 +26,7 @@ enum BorderPart {
     RightBottomCorner,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
@@ -40,23 +40,71 @@ impl Point {
     }
 }
 
+/// A box around the node name. Boxes with many arrows are made larger than
+/// the name needs, so every arrow gets its own cell on the border.
 #[derive(Debug, PartialEq)]
 pub struct Node {
     pub name: String,
     pub border: BorderType,
+    pub line: usize,
+    pub width: usize,
+    pub height: usize,
 }
 impl Node {
+    pub fn new(name: &str, line: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            border: BorderType::Box,
+            line,
+            width: name.chars().count() + 4,
+            height: 3,
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    fn get_text_anchor(&self, anchor: &Point) -> Point {
+        Point::new_with_anchor(
+            (self.width - self.name.chars().count()) / 2,
+            self.height / 2,
+            anchor,
+        )
+    }
+
+    fn get_cell_kind(&self, anchor: &Point, point: &Point) -> CellKind {
+        let text_anchor = self.get_text_anchor(anchor);
+        let text_end = text_anchor.x + self.name.chars().count();
+        if point.y == text_anchor.y && point.x >= text_anchor.x && point.x < text_end {
+            CellKind::NodeText
+        } else {
+            CellKind::NodeBorder
+        }
+    }
+
     fn grab_characters(&self, anchor: &Point) -> HashMap<Point, char> {
         let mut output = HashMap::new();
-        let end_x = self.name.len() + 3;
-        self.add_corners(end_x, anchor, &mut output);
-        self.add_top_and_bottom(end_x, anchor, &mut output);
-        self.add_sides(end_x, anchor, &mut output);
+        let end_x = self.get_width() - 1;
+        let end_y = self.get_height() - 1;
+        self.add_corners(end_x, end_y, anchor, &mut output);
+        self.add_top_and_bottom(end_x, end_y, anchor, &mut output);
+        self.add_sides(end_x, end_y, anchor, &mut output);
         self.add_text(anchor, &mut output);
         output
     }
 
-    fn add_corners(&self, end_x: usize, anchor: &Point, output: &mut HashMap<Point, char>) {
+    fn add_corners(
+        &self,
+        end_x: usize,
+        end_y: usize,
+        anchor: &Point,
+        output: &mut HashMap<Point, char>,
+    ) {
         output.insert(
             Point::new_with_anchor(0, 0, anchor),
             self.border.get_matching_char(&BorderPart::LeftTopCorner),
@@ -66,42 +114,62 @@ impl Node {
             self.border.get_matching_char(&BorderPart::RightTopCorner),
         );
         output.insert(
-            Point::new_with_anchor(0, 2, anchor),
+            Point::new_with_anchor(0, end_y, anchor),
             self.border.get_matching_char(&BorderPart::LeftBottomCorner),
         );
         output.insert(
-            Point::new_with_anchor(end_x, 2, anchor),
+            Point::new_with_anchor(end_x, end_y, anchor),
             self.border
                 .get_matching_char(&BorderPart::RightBottomCorner),
         );
     }
-    fn add_top_and_bottom(&self, end_x: usize, anchor: &Point, output: &mut HashMap<Point, char>) {
+    fn add_top_and_bottom(
+        &self,
+        end_x: usize,
+        end_y: usize,
+        anchor: &Point,
+        output: &mut HashMap<Point, char>,
+    ) {
         for x in 1..end_x {
             output.insert(
                 Point::new_with_anchor(x, 0, anchor),
                 self.border.get_matching_char(&BorderPart::Horizontal),
             );
             output.insert(
-                Point::new_with_anchor(x, 2, anchor),
+                Point::new_with_anchor(x, end_y, anchor),
                 self.border.get_matching_char(&BorderPart::Horizontal),
             );
         }
     }
-    fn add_sides(&self, end_x: usize, anchor: &Point, output: &mut HashMap<Point, char>) {
-        output.insert(
-            Point::new_with_anchor(0, 1, anchor),
-            self.border.get_matching_char(&BorderPart::Vertical),
-        );
-        output.insert(
-            Point::new_with_anchor(end_x, 1, anchor),
-            self.border.get_matching_char(&BorderPart::Vertical),
-        );
+    fn add_sides(
+        &self,
+        end_x: usize,
+        end_y: usize,
+        anchor: &Point,
+        output: &mut HashMap<Point, char>,
+    ) {
+        for y in 1..end_y {
+            output.insert(
+                Point::new_with_anchor(0, y, anchor),
+                self.border.get_matching_char(&BorderPart::Vertical),
+            );
+            output.insert(
+                Point::new_with_anchor(end_x, y, anchor),
+                self.border.get_matching_char(&BorderPart::Vertical),
+            );
+        }
     }
     fn add_text(&self, anchor: &Point, output: &mut HashMap<Point, char>) {
-        self.name.chars().fold(2, |acc, c| {
-            output.insert(Point::new_with_anchor(acc, 1, anchor), c);
-            acc + 1
-        });
+        let text_anchor = self.get_text_anchor(anchor);
+        for (index, c) in self.name.chars().enumerate() {
+            output.insert(
+                Point {
+                    x: text_anchor.x + index,
+                    y: text_anchor.y,
+                },
+                c,
+            );
+        }
     }
 }
 
@@ -112,7 +180,7 @@ enum ArrowDirection {
     VerticalDown,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ArrowBody {
     Basic,
 }
@@ -127,7 +195,7 @@ impl ArrowBody {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ArrowHead {
     Basic,
 }
@@ -142,21 +210,51 @@ impl ArrowHead {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+/// An arrow drawn through orthogonal segments between consecutive points,
+/// with the head on the last point.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Arrow {
-    pub start: Point,
-    pub middle: Point,
-    pub end: Point,
+    pub from: String,
+    pub to: String,
+    pub line: usize,
+    pub points: Vec<Point>,
     pub body: ArrowBody,
     pub head: ArrowHead,
 }
 impl Arrow {
+    pub fn new(from: &str, to: &str, line: usize, start: Point, middle: Point, end: Point) -> Self {
+        Self {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            line,
+            points: vec![start, middle, end],
+            body: ArrowBody::Basic,
+            head: ArrowHead::Basic,
+        }
+    }
+
+    pub fn get_start(&self) -> &Point {
+        &self.points[0]
+    }
+
+    pub fn get_end(&self) -> &Point {
+        &self.points[self.points.len() - 1]
+    }
+
     fn grab_characters(&self) -> HashMap<Point, char> {
         let mut output = HashMap::new();
-        self.add_arrow_body(&self.start, &self.middle, &mut output);
-        self.add_arrow_body(&self.middle, &self.end, &mut output);
-        let end_diff_x = self.end.x as isize - self.middle.x as isize;
-        let end_diff_y = self.end.y as isize - self.middle.y as isize;
+        for segment in self.points.windows(2) {
+            self.add_arrow_body(&segment[0], &segment[1], &mut output);
+        }
+        let end = self.get_end();
+        let head_base = self
+            .points
+            .iter()
+            .rev()
+            .find(|point| *point != end)
+            .unwrap_or(end);
+        let end_diff_x = end.x as isize - head_base.x as isize;
+        let end_diff_y = end.y as isize - head_base.y as isize;
         let arrow_head_char = match (end_diff_y, end_diff_x) {
             (0, a) if a < 0 => self
                 .head
@@ -171,13 +269,7 @@ impl Arrow {
                 .head
                 .get_matching_character(&ArrowDirection::VerticalDown),
         };
-        output.insert(
-            Point {
-                x: self.end.x,
-                y: self.end.y,
-            },
-            arrow_head_char,
-        );
+        output.insert(end.clone(), arrow_head_char);
         output
     }
     fn add_arrow_body(&self, start: &Point, end: &Point, output: &mut HashMap<Point, char>) {
@@ -220,40 +312,277 @@ impl Arrow {
     }
 }
 
+pub trait Renderer {
+    type Output;
+
+    fn begin(&mut self, width: usize, height: usize);
+    fn add_arrow(&mut self, arrow: &Arrow);
+    fn add_node(&mut self, anchor: &Point, node: &Node);
+    fn finish(self) -> Self::Output;
+}
+
+pub struct Canvas<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+impl<T: Clone> Canvas<T> {
+    pub fn new(width: usize, height: usize, empty: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![empty; width * height],
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, point: &Point) -> Option<&T> {
+        if point.x < self.width && point.y < self.height {
+            Some(&self.cells[point.y * self.width + point.x])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, point: &Point, value: T) {
+        if point.x < self.width && point.y < self.height {
+            self.cells[point.y * self.width + point.x] = value;
+        }
+    }
+
+    pub fn get_row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+impl Canvas<char> {
+    pub fn get_picture(&self) -> String {
+        let mut builder = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            builder.extend(self.get_row(y));
+            builder.push('\n');
+        }
+        builder
+    }
+}
+
+pub struct AsciiRenderer {
+    canvas: Canvas<char>,
+}
+impl AsciiRenderer {
+    pub fn new() -> Self {
+        Self {
+            canvas: Canvas::new(0, 0, ' '),
+        }
+    }
+}
+impl Default for AsciiRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Renderer for AsciiRenderer {
+    type Output = String;
+
+    fn begin(&mut self, width: usize, height: usize) {
+        self.canvas = Canvas::new(width, height, ' ');
+    }
+
+    fn add_arrow(&mut self, arrow: &Arrow) {
+        for (point, c) in arrow.grab_characters() {
+            self.canvas.set(&point, c);
+        }
+    }
+
+    fn add_node(&mut self, anchor: &Point, node: &Node) {
+        for (point, c) in node.grab_characters(anchor) {
+            self.canvas.set(&point, c);
+        }
+    }
+
+    fn finish(self) -> String {
+        self.canvas.get_picture()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellKind {
+    Empty,
+    NodeBorder,
+    NodeText,
+    ArrowBody,
+    ArrowHead,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellOwner {
+    Node { name: String },
+    Edge { from: String, to: String },
+}
+
+/// A single character of the picture together with the element that drew it
+/// and the input line that element came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub kind: CellKind,
+    pub owner: Option<CellOwner>,
+    pub line: Option<usize>,
+}
+impl Cell {
+    fn empty() -> Self {
+        Self {
+            glyph: ' ',
+            kind: CellKind::Empty,
+            owner: None,
+            line: None,
+        }
+    }
+}
+
+pub struct CellRenderer {
+    canvas: Canvas<Cell>,
+}
+impl CellRenderer {
+    pub fn new() -> Self {
+        Self {
+            canvas: Canvas::new(0, 0, Cell::empty()),
+        }
+    }
+}
+impl Default for CellRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Renderer for CellRenderer {
+    type Output = Canvas<Cell>;
+
+    fn begin(&mut self, width: usize, height: usize) {
+        self.canvas = Canvas::new(width, height, Cell::empty());
+    }
+
+    fn add_arrow(&mut self, arrow: &Arrow) {
+        for (point, glyph) in arrow.grab_characters() {
+            let kind = if point == *arrow.get_end() {
+                CellKind::ArrowHead
+            } else {
+                CellKind::ArrowBody
+            };
+            let cell = Cell {
+                glyph,
+                kind,
+                owner: Some(CellOwner::Edge {
+                    from: arrow.from.to_owned(),
+                    to: arrow.to.to_owned(),
+                }),
+                line: Some(arrow.line),
+            };
+            self.canvas.set(&point, cell);
+        }
+    }
+
+    fn add_node(&mut self, anchor: &Point, node: &Node) {
+        for (point, glyph) in node.grab_characters(anchor) {
+            let cell = Cell {
+                glyph,
+                kind: node.get_cell_kind(anchor, &point),
+                owner: Some(CellOwner::Node {
+                    name: node.name.to_owned(),
+                }),
+                line: Some(node.line),
+            };
+            self.canvas.set(&point, cell);
+        }
+    }
+
+    fn finish(self) -> Canvas<Cell> {
+        self.canvas
+    }
+}
+
+/// Whether two boxes, given by their top left corners and their sizes, leave
+/// fewer than `gap` free cells between them. With no gap they overlap.
+pub fn boxes_overlap(
+    anchor: &Point,
+    size: (usize, usize),
+    other_anchor: &Point,
+    other_size: (usize, usize),
+    gap: usize,
+) -> bool {
+    anchor.x < other_anchor.x + other_size.0 + gap
+        && other_anchor.x < anchor.x + size.0 + gap
+        && anchor.y < other_anchor.y + other_size.1 + gap
+        && other_anchor.y < anchor.y + size.1 + gap
+}
+
+/// Nodes and arrows are kept ordered, so every pass over them, and with it
+/// the output, is the same between runs.
 #[derive(Debug, PartialEq)]
 pub struct Map {
-    pub nodes: HashMap<Point, Node>,
-    pub arrows: HashSet<Arrow>,
+    pub nodes: BTreeMap<Point, Node>,
+    pub arrows: BTreeSet<Arrow>,
+    /// Problems found while building the map that did not stop it, such as
+    /// arrows that could not be drawn.
+    pub diagnostics: Vec<String>,
 }
 impl Map {
-    pub fn get_picture(&self) -> String {
-        let mut chars: HashMap<Point, char> = HashMap::new();
+    pub fn get_size(&self) -> (usize, usize) {
+        let mut max_x = 0;
+        let mut max_y = 0;
+        for (anchor, node) in &self.nodes {
+            max_x = max_x.max(anchor.x + node.get_width() - 1);
+            max_y = max_y.max(anchor.y + node.get_height() - 1);
+        }
         for arrow in &self.arrows {
-            let characters = arrow.grab_characters();
-            for character in characters {
-                chars.insert(character.0, character.1);
+            for point in &arrow.points {
+                max_x = max_x.max(point.x);
+                max_y = max_y.max(point.y);
             }
         }
-        for node in &self.nodes {
-            let characters = node.1.grab_characters(node.0);
-            for character in characters {
-                chars.insert(character.0, character.1);
-            }
+        (max_x + 1, max_y + 1)
+    }
+
+    /// Feeds arrows first and nodes second, both sorted by position, so
+    /// renderers can paint boxes over arrow bodies.
+    pub fn render<R: Renderer>(&self, mut renderer: R) -> R::Output {
+        let (width, height) = self.get_size();
+        renderer.begin(width, height);
+
+        let mut arrows = self.arrows.iter().collect::<Vec<_>>();
+        arrows.sort_by_cached_key(|arrow| {
+            let (start, end) = (arrow.get_start(), arrow.get_end());
+            let waypoints = arrow
+                .points
+                .iter()
+                .map(|point| (point.y, point.x))
+                .collect::<Vec<_>>();
+            (start.y, start.x, end.y, end.x, waypoints)
+        });
+        for arrow in arrows {
+            renderer.add_arrow(arrow);
         }
-        let mut builder = String::new();
-        let max_x = chars.iter().max_by_key(|c| c.0.x).unwrap().0.x;
-        let max_y = chars.iter().max_by_key(|c| c.0.y).unwrap().0.y;
-        for y in 0..=max_y {
-            for x in 0..=max_x {
-                let potential_char = chars.iter().find(|c| c.0.x == x && c.0.y == y);
-                match potential_char {
-                    None => builder.push(' '),
-                    Some(c) => builder.push(*c.1),
-                }
-            }
-            builder.push('\n');
+
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|(anchor, _)| (anchor.y, anchor.x));
+        for (anchor, node) in nodes {
+            renderer.add_node(anchor, node);
         }
-        builder
+        renderer.finish()
+    }
+
+    pub fn get_picture(&self) -> String {
+        self.render(AsciiRenderer::new())
+    }
+
+    pub fn get_cells(&self) -> Canvas<Cell> {
+        self.render(CellRenderer::new())
     }
 }
 
@@ -261,39 +590,109 @@ impl Map {
 mod tests {
     use super::*;
 
+    fn get_grid_map(size: usize) -> Map {
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        let mut arrows: BTreeSet<Arrow> = BTreeSet::new();
+        for row in 0..size {
+            for column in 0..size {
+                nodes.insert(
+                    Point {
+                        x: column * 10,
+                        y: row * 5,
+                    },
+                    Node::new("N", 1),
+                );
+                if column > 0 {
+                    arrows.insert(Arrow::new(
+                        "N",
+                        "N",
+                        1,
+                        Point {
+                            x: column * 10 - 5,
+                            y: row * 5 + 1,
+                        },
+                        Point {
+                            x: column * 10 - 4,
+                            y: row * 5 + 1,
+                        },
+                        Point {
+                            x: column * 10 - 1,
+                            y: row * 5 + 1,
+                        },
+                    ));
+                }
+            }
+        }
+        Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Counts the characters the map hands to a renderer.
+    struct WriteCounter(usize);
+    impl Renderer for WriteCounter {
+        type Output = usize;
+
+        fn begin(&mut self, _width: usize, _height: usize) {}
+        fn add_arrow(&mut self, arrow: &Arrow) {
+            self.0 += arrow.grab_characters().len();
+        }
+        fn add_node(&mut self, anchor: &Point, node: &Node) {
+            self.0 += node.grab_characters(anchor).len();
+        }
+        fn finish(self) -> usize {
+            self.0
+        }
+    }
+
     #[test]
-    fn map_returns_with_two_arrows() {
-        let mut nodes: HashMap<Point, Node> = HashMap::new();
-        nodes.insert(
-            Point { x: 0, y: 0 },
-            Node {
-                name: "A".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 10, y: 0 },
-            Node {
-                name: "B".to_owned(),
-                border: BorderType::Box,
-            },
+    fn map_renders_large_maps_in_linear_time() {
+        let small = get_grid_map(20);
+        let large = get_grid_map(80);
+
+        // The large map has 16 times more cells, and every cell is written
+        // once per element covering it.
+        let small_writes = small.render(WriteCounter(0));
+        let large_writes = large.render(WriteCounter(0));
+        assert!(
+            large_writes <= small_writes * 17,
+            "small: {}, large: {}",
+            small_writes,
+            large_writes
         );
-        let mut arrows: HashSet<Arrow> = HashSet::new();
-        arrows.insert(Arrow {
-            start: Point { x: 6, y: 1 },
-            middle: Point { x: 7, y: 1 },
-            end: Point { x: 8, y: 1 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 12, y: 3 },
-            middle: Point { x: 12, y: 5 },
-            end: Point { x: 2, y: 3 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        let map = Map { nodes, arrows };
+        let (width, height) = large.get_size();
+        assert_eq!(large.get_picture().len(), (width + 1) * height);
+    }
+
+    #[test]
+    fn map_returns_with_two_arrows() {
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        nodes.insert(Point { x: 0, y: 0 }, Node::new("A", 1));
+        nodes.insert(Point { x: 10, y: 0 }, Node::new("B", 1));
+        let mut arrows: BTreeSet<Arrow> = BTreeSet::new();
+        arrows.insert(Arrow::new(
+            "A",
+            "B",
+            1,
+            Point { x: 6, y: 1 },
+            Point { x: 7, y: 1 },
+            Point { x: 8, y: 1 },
+        ));
+        arrows.insert(Arrow::new(
+            "B",
+            "A",
+            1,
+            Point { x: 12, y: 3 },
+            Point { x: 12, y: 5 },
+            Point { x: 2, y: 3 },
+        ));
+        let map = Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
         let expected = "\
 +---+     +---+
 | A | --> | B |
@@ -307,31 +706,60 @@ mod tests {
     }
 
     #[test]
-    fn map_returns_with_arrow() {
-        let mut nodes: HashMap<Point, Node> = HashMap::new();
-        nodes.insert(
-            Point { x: 0, y: 0 },
-            Node {
-                name: "A".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 10, y: 0 },
-            Node {
-                name: "B".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<Arrow> = HashSet::new();
+    fn map_returns_with_polyline_arrow() {
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        for (name, x) in [("A", 0), ("C", 10), ("B", 20)] {
+            nodes.insert(Point { x, y: 0 }, Node::new(name, 1));
+        }
+        let mut arrows: BTreeSet<Arrow> = BTreeSet::new();
         arrows.insert(Arrow {
-            start: Point { x: 6, y: 1 },
-            middle: Point { x: 7, y: 1 },
-            end: Point { x: 8, y: 1 },
+            from: "A".to_owned(),
+            to: "B".to_owned(),
+            line: 1,
+            points: vec![
+                Point { x: 2, y: 3 },
+                Point { x: 2, y: 4 },
+                Point { x: 22, y: 4 },
+                Point { x: 22, y: 3 },
+            ],
             body: ArrowBody::Basic,
             head: ArrowHead::Basic,
         });
-        let map = Map { nodes, arrows };
+        let map = Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
+        let expected = "\
++---+     +---+     +---+
+| A |     | C |     | B |
++---+     +---+     +---+
+  |                   ^  
+  --------------------|  
+";
+        let output = map.get_picture();
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn map_returns_with_arrow() {
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        nodes.insert(Point { x: 0, y: 0 }, Node::new("A", 1));
+        nodes.insert(Point { x: 10, y: 0 }, Node::new("B", 1));
+        let mut arrows: BTreeSet<Arrow> = BTreeSet::new();
+        arrows.insert(Arrow::new(
+            "A",
+            "B",
+            1,
+            Point { x: 6, y: 1 },
+            Point { x: 7, y: 1 },
+            Point { x: 8, y: 1 },
+        ));
+        let map = Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
         let expected = "\
 +---+     +---+
 | A | --> | B |
@@ -343,24 +771,13 @@ mod tests {
 
     #[test]
     fn map_returns_two_nodes() {
-        let mut nodes: HashMap<Point, Node> = HashMap::new();
-        nodes.insert(
-            Point { x: 0, y: 0 },
-            Node {
-                name: "A".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 5, y: 0 },
-            Node {
-                name: "B".to_owned(),
-                border: BorderType::Box,
-            },
-        );
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        nodes.insert(Point { x: 0, y: 0 }, Node::new("A", 1));
+        nodes.insert(Point { x: 5, y: 0 }, Node::new("B", 1));
         let map = Map {
             nodes,
-            arrows: HashSet::new(),
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
         };
         let expected = "\
 +---++---+
@@ -373,93 +790,67 @@ mod tests {
 
     #[test]
     fn map_returns_complex() {
-        let mut nodes: HashMap<Point, Node> = HashMap::new();
-        nodes.insert(
-            Point { x: 0, y: 0 },
-            Node {
-                name: "A".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 10, y: 0 },
-            Node {
-                name: "B".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 20, y: 0 },
-            Node {
-                name: "C".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 30, y: 0 },
-            Node {
-                name: "D".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 20, y: 4 },
-            Node {
-                name: "E".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        nodes.insert(
-            Point { x: 30, y: 4 },
-            Node {
-                name: "F".to_owned(),
-                border: BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<Arrow> = HashSet::new();
-        arrows.insert(Arrow {
-            start: Point { x: 6, y: 1 },
-            middle: Point { x: 7, y: 1 },
-            end: Point { x: 8, y: 1 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 16, y: 1 },
-            middle: Point { x: 17, y: 1 },
-            end: Point { x: 18, y: 1 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 26, y: 1 },
-            middle: Point { x: 27, y: 1 },
-            end: Point { x: 28, y: 1 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 16, y: 5 },
-            middle: Point { x: 17, y: 5 },
-            end: Point { x: 18, y: 5 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 26, y: 5 },
-            middle: Point { x: 27, y: 5 },
-            end: Point { x: 28, y: 5 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        arrows.insert(Arrow {
-            start: Point { x: 12, y: 3 },
-            middle: Point { x: 12, y: 5 },
-            end: Point { x: 18, y: 5 },
-            body: ArrowBody::Basic,
-            head: ArrowHead::Basic,
-        });
-        let map = Map { nodes, arrows };
+        let mut nodes: BTreeMap<Point, Node> = BTreeMap::new();
+        nodes.insert(Point { x: 0, y: 0 }, Node::new("A", 1));
+        nodes.insert(Point { x: 10, y: 0 }, Node::new("B", 1));
+        nodes.insert(Point { x: 20, y: 0 }, Node::new("C", 1));
+        nodes.insert(Point { x: 30, y: 0 }, Node::new("D", 1));
+        nodes.insert(Point { x: 20, y: 4 }, Node::new("E", 1));
+        nodes.insert(Point { x: 30, y: 4 }, Node::new("F", 1));
+        let mut arrows: BTreeSet<Arrow> = BTreeSet::new();
+        arrows.insert(Arrow::new(
+            "A",
+            "B",
+            1,
+            Point { x: 6, y: 1 },
+            Point { x: 7, y: 1 },
+            Point { x: 8, y: 1 },
+        ));
+        arrows.insert(Arrow::new(
+            "B",
+            "C",
+            1,
+            Point { x: 16, y: 1 },
+            Point { x: 17, y: 1 },
+            Point { x: 18, y: 1 },
+        ));
+        arrows.insert(Arrow::new(
+            "C",
+            "D",
+            1,
+            Point { x: 26, y: 1 },
+            Point { x: 27, y: 1 },
+            Point { x: 28, y: 1 },
+        ));
+        arrows.insert(Arrow::new(
+            "B",
+            "E",
+            1,
+            Point { x: 16, y: 5 },
+            Point { x: 17, y: 5 },
+            Point { x: 18, y: 5 },
+        ));
+        arrows.insert(Arrow::new(
+            "E",
+            "F",
+            1,
+            Point { x: 26, y: 5 },
+            Point { x: 27, y: 5 },
+            Point { x: 28, y: 5 },
+        ));
+        arrows.insert(Arrow::new(
+            "B",
+            "E",
+            1,
+            Point { x: 12, y: 3 },
+            Point { x: 12, y: 5 },
+            Point { x: 18, y: 5 },
+        ));
+        let map = Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
         let expected = "\
 +---+     +---+     +---+     +---+
 | A | --> | B | --> | C | --> | D |
@@ -475,10 +866,7 @@ mod tests {
 
     #[test]
     fn node_returns() {
-        let node = Node {
-            name: "T".to_owned(),
-            border: BorderType::Box,
-        };
+        let node = Node::new("T", 1);
         let anchor = Point { x: 0, y: 0 };
         let output = node.grab_characters(&anchor);
 
@@ -508,4 +896,23 @@ mod tests {
             assert_eq!(matching_output.len(), 1);
         }
     }
+
+    #[test]
+    fn node_fits_names_by_characters() {
+        let node = Node::new("Größe", 1);
+        assert_eq!(node.get_width(), 9);
+        assert_eq!(
+            node.get_text_anchor(&Point { x: 0, y: 0 }),
+            Point { x: 2, y: 1 }
+        );
+    }
+
+    #[test]
+    fn boxes_overlap_within_the_gap() {
+        let (anchor, other_anchor) = (Point { x: 0, y: 0 }, Point { x: 7, y: 1 });
+        assert!(!boxes_overlap(&anchor, (5, 3), &other_anchor, (5, 3), 0));
+        assert!(!boxes_overlap(&anchor, (5, 3), &other_anchor, (5, 3), 2));
+        assert!(boxes_overlap(&anchor, (5, 3), &other_anchor, (5, 3), 3));
+        assert!(boxes_overlap(&anchor, (8, 3), &other_anchor, (5, 3), 0));
+    }
 }
@@ -0,0 +1,109 @@
+use crate::graphust::domain;
+use crate::graphust::svg;
+
+const STYLE: &str = "\
+    body { margin: 1em; }
+    .arrow { transition: stroke 0.1s; }
+    .node.highlighted rect { stroke: #d33; stroke-width: 2; }
+    .arrow.incoming { stroke: #36c; stroke-width: 2; }
+    .arrow.outgoing { stroke: #d33; stroke-width: 2; }
+    .node { cursor: pointer; }
+";
+
+const SCRIPT: &str = "\
+    const arrows = document.querySelectorAll('.arrow');
+    document.querySelectorAll('.node').forEach(function (node) {
+      const name = node.dataset.node;
+      node.addEventListener('mouseenter', function () {
+        node.classList.add('highlighted');
+        arrows.forEach(function (arrow) {
+          if (arrow.dataset.to === name) {
+            arrow.classList.add('incoming');
+          }
+          if (arrow.dataset.from === name) {
+            arrow.classList.add('outgoing');
+          }
+        });
+      });
+      node.addEventListener('mouseleave', function () {
+        node.classList.remove('highlighted');
+        arrows.forEach(function (arrow) {
+          arrow.classList.remove('incoming', 'outgoing');
+        });
+      });
+    });
+";
+
+pub struct HtmlRenderer {
+    svg: svg::SvgRenderer,
+}
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self {
+            svg: svg::SvgRenderer::new(),
+        }
+    }
+}
+impl Default for HtmlRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl domain::Renderer for HtmlRenderer {
+    type Output = String;
+
+    fn begin(&mut self, width: usize, height: usize) {
+        self.svg.begin(width, height);
+    }
+
+    fn add_arrow(&mut self, arrow: &domain::Arrow) {
+        self.svg.add_arrow(arrow);
+    }
+
+    fn add_node(&mut self, anchor: &domain::Point, node: &domain::Node) {
+        self.svg.add_node(anchor, node);
+    }
+
+    fn finish(self) -> String {
+        let mut builder = String::new();
+        builder.push_str("<!DOCTYPE html>\n");
+        builder.push_str("<html>\n<head>\n<meta charset=\"utf-8\">\n<title>graphust</title>\n");
+        builder.push_str("<style>\n");
+        builder.push_str(STYLE);
+        builder.push_str("</style>\n</head>\n<body>\n");
+        builder.push_str(&self.svg.finish());
+        builder.push_str("<script>\n");
+        builder.push_str(SCRIPT);
+        builder.push_str("</script>\n</body>\n</html>\n");
+        builder
+    }
+}
+
+pub fn get_html(map: &domain::Map) -> String {
+    map.render(HtmlRenderer::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn html_embeds_svg_and_script() {
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 0 }, domain::Node::new("A", 1));
+        let map = domain::Map {
+            nodes,
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        let output = get_html(&map);
+
+        assert!(output.starts_with("<!DOCTYPE html>\n"));
+        assert!(output.contains(&svg::get_svg(&map)));
+        assert!(output.contains("<script>\n"));
+        assert!(!output.contains("src="));
+        assert!(!output.contains("href="));
+        assert!(output.ends_with("</html>\n"));
+    }
+}
@@ -1,17 +1,80 @@
 use crate::graphust::domain;
-use std::collections::{HashMap, HashSet};
+use crate::graphust::layout;
+use crate::graphust::options;
+use crate::graphust::stats;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-mod force_directed_graph;
+mod router;
 
 #[derive(Debug)]
 struct InnerMapping {
     source: String,
     arrow: String,
     target: String,
+    line: usize,
 }
 
-const BOX_WIDTH: usize = 4;
-const BOX_HEIGHT: usize = 3;
+#[derive(Debug)]
+enum InnerConstraintKind {
+    Pin(usize, usize),
+    LeftOf,
+    Above,
+    SameRank,
+    Aligned,
+}
+
+#[derive(Debug)]
+struct InnerConstraint {
+    kind: InnerConstraintKind,
+    nodes: Vec<String>,
+    line: usize,
+}
+
+#[derive(Debug)]
+enum InnerLine {
+    Mapping(InnerMapping),
+    Direction(layout::LayoutDirection),
+    Constraint(InnerConstraint),
+}
+
+const MIN_GAP: usize = 2;
+/// Terminal cells are about twice as tall as they are wide.
+const CELL_ASPECT_RATIO: usize = 2;
+const DEFAULT_SCALE: Scale = Scale {
+    columns: 4,
+    rows: 1,
+};
+/// Keeps the proportions of `DEFAULT_SCALE` once the axes are swapped.
+const TRANSPOSED_SCALE: Scale = Scale {
+    columns: DEFAULT_SCALE.rows * CELL_ASPECT_RATIO,
+    rows: DEFAULT_SCALE.columns / CELL_ASPECT_RATIO,
+};
+const ROUTING_MARGIN: usize = 2;
+/// Limits how often boxes are resized for the sides their arrows took.
+const MAX_SIZING_PASSES: usize = 4;
+
+/// Columns and rows between neighbouring layout positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scale {
+    columns: usize,
+    rows: usize,
+}
+
+/// Arrows are routed in signed coordinates, so they may pass left of or above
+/// every node before the map is shifted back to the origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SignedPoint {
+    x: isize,
+    y: isize,
+}
+impl SignedPoint {
+    fn from_point(point: &domain::Point) -> Self {
+        Self {
+            x: point.x as isize,
+            y: point.y as isize,
+        }
+    }
+}
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone)]
 enum Direction {
@@ -53,33 +116,104 @@ fn get_line_parts_respecting_quotes(line: &str) -> Vec<String> {
     parts
 }
 
-fn get_inner_mappings(text: &str) -> Vec<Result<InnerMapping, String>> {
+fn read_pin(parts: &[String]) -> Option<(usize, usize)> {
+    let position = parts.concat();
+    let (x, y) = position
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+/// Reads `A @ (x, y)`, `A left-of B`, `A right-of B`, `A above B`,
+/// `A below B`, `rank same: A B` and `align: A B`. Other lines are no
+/// constraints.
+fn get_inner_constraint(
+    parts: &[String],
+    line: &str,
+    line_number: usize,
+) -> Result<Option<InnerConstraint>, String> {
+    let (kind, nodes) = match parts {
+        [node, at, position @ ..] if at == "@" => {
+            let Some((x, y)) = read_pin(position) else {
+                return Err(format!("Cannot understand this line: {}", line));
+            };
+            (InnerConstraintKind::Pin(x, y), vec![node.to_owned()])
+        }
+        [rank, same, nodes @ ..] if rank == "rank" && same == "same:" => {
+            (InnerConstraintKind::SameRank, nodes.to_vec())
+        }
+        [align, nodes @ ..] if align == "align:" => (InnerConstraintKind::Aligned, nodes.to_vec()),
+        [first, hint, second] => {
+            let (kind, before, after) = match hint.as_str() {
+                "left-of" => (InnerConstraintKind::LeftOf, first, second),
+                "right-of" => (InnerConstraintKind::LeftOf, second, first),
+                "above" => (InnerConstraintKind::Above, first, second),
+                "below" => (InnerConstraintKind::Above, second, first),
+                _ => return Ok(None),
+            };
+            (kind, vec![before.to_owned(), after.to_owned()])
+        }
+        _ => return Ok(None),
+    };
+    let is_group = matches!(
+        kind,
+        InnerConstraintKind::SameRank | InnerConstraintKind::Aligned
+    );
+    if is_group && nodes.len() < 2 {
+        return Err(format!("Cannot understand this line: {}", line));
+    }
+    Ok(Some(InnerConstraint {
+        kind,
+        nodes,
+        line: line_number,
+    }))
+}
+
+fn get_inner_lines(text: &str) -> Vec<Result<InnerLine, String>> {
     text.lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(index, line)| {
             let parts = get_line_parts_respecting_quotes(line);
+            if parts.len() == 2 && parts[0] == "direction" {
+                return layout::LayoutDirection::from_name(&parts[1]).map(InnerLine::Direction);
+            }
+            if let Some(constraint) = get_inner_constraint(&parts, line, index + 1)? {
+                return Ok(InnerLine::Constraint(constraint));
+            }
             if parts.len() != 3 {
                 return Err(format!("Cannot understand this line: {}", line));
             }
             let arrow_correct_direction = !parts[1].starts_with('<');
             if arrow_correct_direction {
-                Ok(InnerMapping {
+                Ok(InnerLine::Mapping(InnerMapping {
                     source: parts[0].to_owned(),
                     arrow: parts[1].to_owned(),
                     target: parts[2].to_owned(),
-                })
+                    line: index + 1,
+                }))
             } else {
-                Ok(InnerMapping {
+                Ok(InnerLine::Mapping(InnerMapping {
                     source: parts[2].to_owned(),
                     arrow: parts[1].chars().rev().collect::<String>(),
                     target: parts[0].to_owned(),
-                })
+                    line: index + 1,
+                }))
             }
         })
         .collect::<Vec<_>>()
 }
 
+#[cfg(test)]
 pub fn read_input(text: &str) -> Result<domain::Map, String> {
-    let inner_parts = get_inner_mappings(text);
+    read_input_with_options(text, &options::Options::default()).map(|(map, _)| map)
+}
+
+pub fn read_input_with_options(
+    text: &str,
+    options: &options::Options,
+) -> Result<(domain::Map, layout::LayoutCache), String> {
+    let inner_parts = get_inner_lines(text);
     if inner_parts.iter().any(|x| x.is_err()) {
         return Err(inner_parts
             .iter()
@@ -89,165 +223,923 @@ pub fn read_input(text: &str) -> Result<domain::Map, String> {
             .unwrap_err()
             .to_owned());
     }
-    let inner_mapping = inner_parts
+    let inner_lines = inner_parts
         .iter()
         .map(|x| x.as_ref().unwrap())
         .collect::<Vec<_>>();
+    let inner_mapping = inner_lines
+        .iter()
+        .filter_map(|x| match x {
+            InnerLine::Mapping(mapping) => Some(mapping),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let directive = inner_lines.iter().rev().find_map(|x| match x {
+        InnerLine::Direction(direction) => Some(*direction),
+        _ => None,
+    });
+    let direction = options
+        .layout
+        .get_direction(options.direction.or(directive));
 
-    let mut force_directed_graph = force_directed_graph::Graph::new();
-    for mapping in &inner_mapping {
-        force_directed_graph.add_node(&mapping.source);
-        force_directed_graph.add_node(&mapping.target);
-        force_directed_graph.add_edge(&mapping.source, &mapping.target);
+    let inner_constraints = inner_lines
+        .iter()
+        .filter_map(|x| match x {
+            InnerLine::Constraint(constraint) => Some(constraint),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let (inner_constraints, pin_diagnostics) = remove_clashing_pins(inner_constraints);
+
+    let (nodes, edges) = get_nodes_and_edges(&inner_mapping);
+    let mut constraints = get_constraints(&inner_constraints, &nodes)?;
+    // The cache holds the layout before it is turned to fit, so kept
+    // positions are drawn the same way again and still allow turning.
+    let can_reorient = constraints.is_empty();
+    if let Some(previous_layout) = &options.previous_layout {
+        add_kept_positions(&mut constraints, &nodes, previous_layout);
     }
-    let approximation = force_directed_graph.force_directed(None, None);
-    let mut map = domain::Map {
-        nodes: HashMap::new(),
-        arrows: HashSet::new(),
+    let fixed_nodes = constraints
+        .iter()
+        .filter_map(|constraint| match constraint {
+            layout::LayoutConstraint::Pin { node, .. }
+            | layout::LayoutConstraint::Keep { node, .. } => Some(nodes[*node].as_str()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    let measure = |approximation: &[layout::NodeApproximation]| {
+        let map = get_fitting_map(
+            approximation.to_vec(),
+            &inner_mapping,
+            direction,
+            options,
+            can_reorient,
+            &fixed_nodes,
+        );
+        stats::get_stats(&map)
     };
-    include_nodes(&mut map, &approximation);
-    include_arrows(&mut map, &inner_mapping);
-    Ok(map)
+    let approximation = options.layout.layout(&layout::LayoutInput {
+        nodes: &nodes,
+        edges: &edges,
+        direction,
+        constraints: &constraints,
+        measure: &measure,
+    });
+    let mut map = get_fitting_map(
+        approximation.clone(),
+        &inner_mapping,
+        direction,
+        options,
+        can_reorient,
+        &fixed_nodes,
+    );
+    map.diagnostics.splice(0..0, pin_diagnostics);
+    Ok((map, layout::LayoutCache::from_approximation(&approximation)))
 }
 
-fn include_nodes(
-    map: &mut domain::Map,
-    approximation: &Vec<force_directed_graph::NodeApproximation>,
-) {
-    for node in approximation {
-        map.nodes.insert(
-            domain::Point {
-                x: node.position.x * 4,
-                y: node.position.y,
+/// Boxes are first sized for the sides arrows are expected to use. Arrows
+/// often end up on other sides, so the boxes are sized again for the sides
+/// the routes took and the arrows routed anew, growing them until every side
+/// has a cell for each of its arrows.
+fn get_map(
+    approximation: &[layout::NodeApproximation],
+    inner_mappings: &Vec<&InnerMapping>,
+    layout_direction: Option<layout::LayoutDirection>,
+    scale: Scale,
+    fixed_nodes: &HashSet<&str>,
+) -> domain::Map {
+    let mut side_counts = None;
+    let mut pass = 1;
+    loop {
+        let mut map = domain::Map {
+            nodes: BTreeMap::new(),
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        let sized_counts = include_nodes(
+            &mut map,
+            approximation,
+            inner_mappings,
+            layout_direction,
+            scale,
+            side_counts.as_ref(),
+            fixed_nodes,
+        );
+        let mut used_counts =
+            include_arrows(&mut map, inner_mappings, layout_direction, &sized_counts);
+        // The first pass only guessed the sides, so its boxes are resized even
+        // when they are too large. Later passes only grow boxes which ran out
+        // of cells.
+        let fits = if pass == 1 {
+            used_counts == sized_counts
+        } else {
+            map.nodes
+                .values()
+                .all(|node| has_room_for_arrows(node, &used_counts[&node.name]))
+        };
+        if fits || pass == MAX_SIZING_PASSES {
+            return map;
+        }
+        if pass > 1 {
+            for (name, counts) in used_counts.iter_mut() {
+                for (direction, count) in sized_counts[name].iter() {
+                    let used_count = counts.entry(*direction).or_default();
+                    *used_count = (*used_count).max(*count);
+                }
+            }
+        }
+        side_counts = Some(used_counts);
+        pass += 1;
+    }
+}
+
+fn has_room_for_arrows(node: &domain::Node, counts: &SideCounts) -> bool {
+    let get_count = |direction| counts.get(&direction).copied().unwrap_or_default();
+    get_count(Direction::Top).max(get_count(Direction::Bottom)) + 2 <= node.get_width()
+        && get_count(Direction::Left).max(get_count(Direction::Right)) + 2 <= node.get_height()
+}
+
+fn get_transposed(approximation: &[layout::NodeApproximation]) -> Vec<layout::NodeApproximation> {
+    approximation
+        .iter()
+        .map(|node| layout::NodeApproximation {
+            name: node.name.clone(),
+            position: layout::PointApproximation {
+                x: node.position.y,
+                y: node.position.x,
             },
-            domain::Node {
-                name: node.name.to_owned(),
-                border: domain::BorderType::Box,
+        })
+        .collect()
+}
+
+/// Draws the layout within the maximum size of the options, first with less
+/// space between nodes and then, if allowed, with the axes
+/// swapped. Keeps the first drawing that fits, or else the one that overflows
+/// least.
+fn get_fitting_map(
+    approximation: Vec<layout::NodeApproximation>,
+    inner_mappings: &Vec<&InnerMapping>,
+    layout_direction: Option<layout::LayoutDirection>,
+    options: &options::Options,
+    can_reorient: bool,
+    fixed_nodes: &HashSet<&str>,
+) -> domain::Map {
+    let mut orientations = vec![(approximation, layout_direction, DEFAULT_SCALE)];
+    if can_reorient && (options.max_width.is_some() || options.max_height.is_some()) {
+        orientations.push((
+            get_transposed(&orientations[0].0),
+            layout_direction.map(|direction| direction.get_transposed()),
+            TRANSPOSED_SCALE,
+        ));
+    }
+
+    let mut best: Option<((usize, usize), domain::Map)> = None;
+    for (approximation, layout_direction, scale) in &orientations {
+        for step in 0..scale.columns.max(scale.rows) {
+            let scale = Scale {
+                columns: scale.columns.saturating_sub(step).max(1),
+                rows: scale.rows.saturating_sub(step).max(1),
+            };
+            let map = get_map(
+                approximation,
+                inner_mappings,
+                *layout_direction,
+                scale,
+                fixed_nodes,
+            );
+            let (width, height) = map.get_size();
+            let overflow = width.saturating_sub(options.max_width.unwrap_or(width))
+                + height.saturating_sub(options.max_height.unwrap_or(height));
+            let rank = (overflow, width * height);
+            if best
+                .as_ref()
+                .is_none_or(|(best_rank, ..)| rank < *best_rank)
+            {
+                best = Some((rank, map));
+            }
+            if overflow == 0 {
+                break;
+            }
+        }
+        if best
+            .as_ref()
+            .is_some_and(|((overflow, _), ..)| *overflow == 0)
+        {
+            break;
+        }
+    }
+    best.unwrap().1
+}
+
+fn get_nodes_and_edges(inner_mappings: &[&InnerMapping]) -> (Vec<String>, Vec<(usize, usize)>) {
+    let mut nodes: Vec<String> = Vec::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let mut edges = Vec::new();
+    for mapping in inner_mappings {
+        for label in [&mapping.source, &mapping.target] {
+            if !indices.contains_key(label.as_str()) {
+                indices.insert(label, nodes.len());
+                nodes.push(label.to_owned());
+            }
+        }
+        edges.push((
+            indices[mapping.source.as_str()],
+            indices[mapping.target.as_str()],
+        ));
+    }
+    (nodes, edges)
+}
+
+/// Keeps the first pin of every cell. Later pins on the same cell are
+/// dropped with a diagnostic, so their nodes are placed freely.
+fn remove_clashing_pins(
+    inner_constraints: Vec<&InnerConstraint>,
+) -> (Vec<&InnerConstraint>, Vec<String>) {
+    let mut pinned_cells: HashMap<(usize, usize), &str> = HashMap::new();
+    let mut diagnostics = Vec::new();
+    let kept = inner_constraints
+        .into_iter()
+        .filter(|constraint| {
+            let InnerConstraintKind::Pin(x, y) = constraint.kind else {
+                return true;
+            };
+            match pinned_cells.get(&(x, y)) {
+                Some(pinned) => {
+                    diagnostics.push(format!(
+                        "Line {}: {} is pinned to the same cell as {}",
+                        constraint.line, constraint.nodes[0], pinned
+                    ));
+                    false
+                }
+                None => {
+                    pinned_cells.insert((x, y), &constraint.nodes[0]);
+                    true
+                }
+            }
+        })
+        .collect();
+    (kept, diagnostics)
+}
+
+fn get_constraints(
+    inner_constraints: &[&InnerConstraint],
+    nodes: &[String],
+) -> Result<Vec<layout::LayoutConstraint>, String> {
+    let mut constraints = Vec::new();
+    for constraint in inner_constraints {
+        let mut indices = Vec::new();
+        for label in &constraint.nodes {
+            let Some(index) = nodes.iter().position(|node| node == label) else {
+                return Err(format!("Line {}: unknown node {}", constraint.line, label));
+            };
+            indices.push(index);
+        }
+        constraints.push(match constraint.kind {
+            InnerConstraintKind::Pin(x, y) => layout::LayoutConstraint::Pin {
+                node: indices[0],
+                x,
+                y,
             },
+            InnerConstraintKind::LeftOf => layout::LayoutConstraint::LeftOf(indices[0], indices[1]),
+            InnerConstraintKind::Above => layout::LayoutConstraint::Above(indices[0], indices[1]),
+            InnerConstraintKind::SameRank => layout::LayoutConstraint::SameRank(indices),
+            InnerConstraintKind::Aligned => layout::LayoutConstraint::Aligned(indices),
+        });
+    }
+    Ok(constraints)
+}
+
+/// Keeps every node of an earlier layout where it was, unless it is pinned or
+/// its cell is taken by a pin or another kept node.
+fn add_kept_positions(
+    constraints: &mut Vec<layout::LayoutConstraint>,
+    nodes: &[String],
+    previous_layout: &layout::LayoutCache,
+) {
+    let mut pinned_nodes = HashSet::new();
+    let mut taken_cells = HashSet::new();
+    for constraint in constraints.iter() {
+        if let layout::LayoutConstraint::Pin { node, x, y } = constraint {
+            pinned_nodes.insert(*node);
+            taken_cells.insert((x * layout::PIN_SPACING, y * layout::PIN_SPACING));
+        }
+    }
+    for (index, node) in nodes.iter().enumerate() {
+        let Some((x, y)) = previous_layout.get(node) else {
+            continue;
+        };
+        if !pinned_nodes.contains(&index) && taken_cells.insert((x, y)) {
+            constraints.push(layout::LayoutConstraint::Keep { node: index, x, y });
+        }
+    }
+}
+
+/// The line on which every node is first mentioned.
+fn get_first_lines<'a>(inner_mappings: &[&'a InnerMapping]) -> HashMap<&'a str, usize> {
+    let mut first_lines = HashMap::new();
+    for mapping in inner_mappings {
+        for label in [&mapping.source, &mapping.target] {
+            first_lines.entry(label.as_str()).or_insert(mapping.line);
+        }
+    }
+    first_lines
+}
+
+/// Places boxes in reading order, pushing each one right or down, whichever
+/// is shorter, until it keeps `MIN_GAP` free cells to every box placed before.
+/// Pinned and kept boxes are placed first, so they are only pushed off each
+/// other and never by free boxes.
+fn remove_overlaps(anchors: &mut [domain::Point], sizes: &[(usize, usize)], is_fixed: &[bool]) {
+    let mut order = (0..anchors.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| (!is_fixed[*index], anchors[*index].y, anchors[*index].x));
+    for (placed_count, index) in order.iter().enumerate() {
+        while let Some(other) = order[..placed_count].iter().copied().find(|other| {
+            domain::boxes_overlap(
+                &anchors[*index],
+                sizes[*index],
+                &anchors[*other],
+                sizes[*other],
+                MIN_GAP,
+            )
+        }) {
+            let push_right = anchors[other].x + sizes[other].0 + MIN_GAP - anchors[*index].x;
+            let push_down = anchors[other].y + sizes[other].1 + MIN_GAP - anchors[*index].y;
+            if push_down < push_right {
+                anchors[*index].y += push_down;
+            } else {
+                anchors[*index].x += push_right;
+            }
+        }
+    }
+}
+
+type SideCounts = HashMap<Direction, usize>;
+
+/// Counts the arrows attached to every side of every node, assuming each
+/// arrow uses its default sides.
+fn get_side_counts(
+    anchors_for_nodes: &[ArrowAnchorsForNode],
+    inner_mappings: &[&InnerMapping],
+    layout_direction: Option<layout::LayoutDirection>,
+) -> Vec<SideCounts> {
+    let indices = anchors_for_nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.node_label, index))
+        .collect::<HashMap<_, _>>();
+    let mut counts = vec![HashMap::new(); anchors_for_nodes.len()];
+    for mapping in inner_mappings {
+        let (Some(from_index), Some(to_index)) = (
+            indices.get(mapping.source.as_str()),
+            indices.get(mapping.target.as_str()),
+        ) else {
+            continue;
+        };
+        let (node_from, node_to) = (
+            &anchors_for_nodes[*from_index],
+            &anchors_for_nodes[*to_index],
         );
+        let directions = if from_index == to_index {
+            Some((Direction::Right, Direction::Top))
+        } else {
+            get_preferred_directions(node_from, node_to, layout_direction)
+                .or_else(|| get_default_directions(node_from, node_to))
+        };
+        if let Some((start_direction, end_direction)) = directions {
+            *counts[*from_index].entry(start_direction).or_insert(0) += 1;
+            *counts[*to_index].entry(end_direction).or_insert(0) += 1;
+        }
     }
+    counts
+}
+
+fn include_nodes(
+    map: &mut domain::Map,
+    approximation: &[layout::NodeApproximation],
+    inner_mappings: &[&InnerMapping],
+    layout_direction: Option<layout::LayoutDirection>,
+    scale: Scale,
+    side_counts: Option<&HashMap<String, SideCounts>>,
+    fixed_nodes: &HashSet<&str>,
+) -> HashMap<String, SideCounts> {
+    let mut anchors = approximation
+        .iter()
+        .map(|node| domain::Point {
+            x: node.position.x * scale.columns,
+            y: node.position.y * scale.rows,
+        })
+        .collect::<Vec<_>>();
+    let first_lines = get_first_lines(inner_mappings);
+    let mut nodes = approximation
+        .iter()
+        .map(|node| {
+            let first_line = first_lines.get(node.name.as_str()).copied();
+            domain::Node::new(&node.name, first_line.unwrap_or_default())
+        })
+        .collect::<Vec<_>>();
+
+    // Boxes grow until every side has a border cell for each of its arrows.
+    let side_counts = match side_counts {
+        Some(side_counts) => side_counts.clone(),
+        None => {
+            let anchors_for_nodes = nodes
+                .iter()
+                .zip(anchors.iter())
+                .map(|(node, anchor)| ArrowAnchorsForNode::new(node, anchor))
+                .collect::<Vec<_>>();
+            let counts = get_side_counts(&anchors_for_nodes, inner_mappings, layout_direction);
+            nodes
+                .iter()
+                .map(|node| node.name.clone())
+                .zip(counts)
+                .collect()
+        }
+    };
+    for node in nodes.iter_mut() {
+        let counts = &side_counts[&node.name];
+        let get_count = |direction| counts.get(&direction).copied().unwrap_or_default();
+        let horizontal_count = get_count(Direction::Top).max(get_count(Direction::Bottom));
+        let vertical_count = get_count(Direction::Left).max(get_count(Direction::Right));
+        node.width = node.width.max(horizontal_count + 2);
+        node.height = node.height.max(vertical_count + 2);
+    }
+
+    let sizes = nodes
+        .iter()
+        .map(|node| (node.get_width(), node.get_height()))
+        .collect::<Vec<_>>();
+    let is_fixed = nodes
+        .iter()
+        .map(|node| fixed_nodes.contains(node.name.as_str()))
+        .collect::<Vec<_>>();
+    remove_overlaps(&mut anchors, &sizes, &is_fixed);
+    for (node, anchor) in nodes.into_iter().zip(anchors) {
+        map.nodes.insert(anchor, node);
+    }
+    side_counts
 }
 
 struct ArrowAnchorsForNode<'a> {
     anchor: &'a domain::Point,
     node_label: &'a str,
-    used_anchors: HashMap<Direction, usize>,
+    width: usize,
+    height: usize,
+    side_counts: SideCounts,
+    /// Offsets along every side taken by arrows so far.
+    used_anchors: HashMap<Direction, Vec<usize>>,
 }
 impl ArrowAnchorsForNode<'_> {
-    fn new<'a>(node_label: &'a str, anchor: &'a domain::Point) -> ArrowAnchorsForNode<'a> {
-        let mut used_anchors = HashMap::new();
-        used_anchors.insert(Direction::Top, 2);
-        used_anchors.insert(Direction::Bottom, 2);
-        used_anchors.insert(Direction::Left, 1);
-        used_anchors.insert(Direction::Right, 1);
+    fn new<'a>(node: &'a domain::Node, anchor: &'a domain::Point) -> ArrowAnchorsForNode<'a> {
         ArrowAnchorsForNode {
             anchor,
-            node_label,
-            used_anchors,
+            node_label: &node.name,
+            width: node.get_width(),
+            height: node.get_height(),
+            side_counts: HashMap::new(),
+            used_anchors: HashMap::new(),
         }
     }
-    fn get_arrow_anchor_offset(&mut self, direction: Direction) -> usize {
-        let output = self.used_anchors.get(&direction).unwrap().to_owned();
-        let next_output = match (direction, output) {
-            (Direction::Left, 2) | (Direction::Right, 2) => 0,
-            _ => output + 1,
-        };
-        self.used_anchors.insert(direction, next_output);
-        output
+    /// Spreads the expected number of arrows evenly along the side, skipping
+    /// the corners. Arrows beyond that take the first free cell. A full side
+    /// hands out its last cell again; the box is then grown and the map drawn
+    /// anew, see [get_map].
+    fn get_arrow_anchor_offset(&self, direction: Direction) -> usize {
+        let length = self.get_side_length(direction);
+        let used = self.get_used_anchors(direction);
+        let count = self
+            .side_counts
+            .get(&direction)
+            .copied()
+            .unwrap_or_default()
+            .clamp(1, length);
+        (0..count)
+            .map(|slot| 1 + (2 * slot + 1) * length / (2 * count))
+            .chain(1..=length)
+            .find(|offset| !used.contains(offset))
+            .unwrap_or(length)
+    }
+    fn get_side_length(&self, direction: Direction) -> usize {
+        match direction {
+            Direction::Top | Direction::Bottom => self.width - 2,
+            Direction::Left | Direction::Right => self.height - 2,
+        }
+    }
+    fn get_used_anchors(&self, direction: Direction) -> &[usize] {
+        self.used_anchors
+            .get(&direction)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+    fn has_free_anchor(&self, direction: Direction) -> bool {
+        self.get_used_anchors(direction).len() < self.get_side_length(direction)
+    }
+    /// The given side while it has a free anchor, otherwise the first side
+    /// which does.
+    fn get_free_direction(&self, direction: Direction) -> Direction {
+        std::iter::once(direction)
+            .chain(ALL_DIRECTIONS)
+            .find(|direction| self.has_free_anchor(*direction))
+            .unwrap_or(direction)
     }
-    fn get_arrow_anchor(&mut self, direction: Direction) -> domain::Point {
+    fn peek_arrow_anchor(&self, direction: Direction) -> SignedPoint {
+        let offset = self.get_arrow_anchor_offset(direction) as isize;
+        let (x, y) = (self.anchor.x as isize, self.anchor.y as isize);
+        match direction {
+            Direction::Top => SignedPoint {
+                x: x + offset,
+                y: y - 1,
+            },
+            Direction::Bottom => SignedPoint {
+                x: x + offset,
+                y: y + self.height as isize,
+            },
+            Direction::Left => SignedPoint {
+                x: x - 1,
+                y: y + offset,
+            },
+            Direction::Right => SignedPoint {
+                x: x + self.width as isize,
+                y: y + offset,
+            },
+        }
+    }
+    fn get_arrow_anchor(&mut self, direction: Direction) -> SignedPoint {
         let offset = self.get_arrow_anchor_offset(direction);
-        let (x, y) = match direction {
-            Direction::Top => (self.anchor.x + offset, self.anchor.y - 1),
-            Direction::Bottom => (self.anchor.x + offset, self.anchor.y + BOX_HEIGHT),
-            Direction::Left => (self.anchor.x - 1, self.anchor.y + offset),
-            Direction::Right => (
-                self.anchor.x + BOX_WIDTH + self.node_label.len(),
-                self.anchor.y + offset,
-            ),
-        };
-        domain::Point { x, y }
+        let anchor = self.peek_arrow_anchor(direction);
+        self.used_anchors.entry(direction).or_default().push(offset);
+        anchor
+    }
+    fn get_used_counts(&self) -> SideCounts {
+        self.used_anchors
+            .iter()
+            .map(|(direction, offsets)| (*direction, offsets.len()))
+            .collect()
     }
 }
 
-fn include_arrows(map: &mut domain::Map, inner_mappings: &Vec<&InnerMapping>) {
-    let mut anchors_for_nodes = vec![];
-    for (anchor, node) in map.nodes.iter() {
-        anchors_for_nodes.push(ArrowAnchorsForNode::new(&node.name, anchor));
+/// Picks the sides matching the layout direction for edges running along it,
+/// e.g. Right to Left for edges pointing rightwards in a left-to-right layout.
+fn get_preferred_directions(
+    node_from: &ArrowAnchorsForNode,
+    node_to: &ArrowAnchorsForNode,
+    layout_direction: Option<layout::LayoutDirection>,
+) -> Option<(Direction, Direction)> {
+    let from_right = node_from.anchor.x + node_from.width;
+    let to_right = node_to.anchor.x + node_to.width;
+    let from_bottom = node_from.anchor.y + node_from.height;
+    let to_bottom = node_to.anchor.y + node_to.height;
+    match layout_direction? {
+        layout::LayoutDirection::LeftToRight if from_right + 1 < node_to.anchor.x => {
+            Some((Direction::Right, Direction::Left))
+        }
+        layout::LayoutDirection::RightToLeft if to_right + 1 < node_from.anchor.x => {
+            Some((Direction::Left, Direction::Right))
+        }
+        layout::LayoutDirection::TopToBottom if from_bottom + 1 < node_to.anchor.y => {
+            Some((Direction::Bottom, Direction::Top))
+        }
+        layout::LayoutDirection::BottomToTop if to_bottom + 1 < node_from.anchor.y => {
+            Some((Direction::Top, Direction::Bottom))
+        }
+        _ => None,
     }
-    for mapping in inner_mappings {
-        let mut mutable_nodes = anchors_for_nodes
-            .iter_mut()
-            .filter(|node| node.node_label == mapping.source || node.node_label == mapping.target);
-        let (node_from, node_to) = match (mutable_nodes.next(), mutable_nodes.next()) {
-            (Some(from), Some(to)) if from.node_label == mapping.source => (from, to),
-            (Some(to), Some(from)) if from.node_label == mapping.source => (from, to),
-            _ => panic!("Could not find nodes for arrow"),
-        };
+}
 
-        let (arrow_start, arrow_end) = match (
-            node_from.anchor.x,
-            node_from.anchor.y,
-            node_to.anchor.x,
-            node_to.anchor.y,
-        ) {
-            (x1, y1, x2, y2) if x1 < x2 && y1 < y2 => (
-                node_from.get_arrow_anchor(Direction::Bottom),
-                node_to.get_arrow_anchor(Direction::Left),
-            ),
-            (x1, y1, x2, y2) if x1 > x2 && y1 > y2 => (
-                node_from.get_arrow_anchor(Direction::Left),
-                node_to.get_arrow_anchor(Direction::Bottom),
-            ),
-            (x1, y1, x2, y2) if x1 > x2 && y1 < y2 => (
-                node_from.get_arrow_anchor(Direction::Bottom),
-                node_to.get_arrow_anchor(Direction::Right),
-            ),
-            (x1, y1, x2, y2) if x1 < x2 && y1 > y2 => (
-                node_from.get_arrow_anchor(Direction::Right),
-                node_to.get_arrow_anchor(Direction::Bottom),
-            ),
-            (x1, y1, x2, y2) if x1 == x2 && y1 < y2 => (
-                node_from.get_arrow_anchor(Direction::Bottom),
-                node_to.get_arrow_anchor(Direction::Top),
-            ),
-            (x1, y1, x2, y2) if x1 == x2 && y1 > y2 => (
-                node_from.get_arrow_anchor(Direction::Top),
-                node_to.get_arrow_anchor(Direction::Bottom),
-            ),
-            (x1, y1, x2, y2) if x1 < x2 && y1 == y2 => (
-                node_from.get_arrow_anchor(Direction::Right),
-                node_to.get_arrow_anchor(Direction::Left),
-            ),
-            (x1, y1, x2, y2) if x1 > x2 && y1 == y2 => (
-                node_from.get_arrow_anchor(Direction::Left),
-                node_to.get_arrow_anchor(Direction::Right),
-            ),
-            _ => continue,
-        };
+fn get_default_middle(arrow_start: &SignedPoint, arrow_end: &SignedPoint) -> Option<SignedPoint> {
+    match (arrow_start.x, arrow_start.y, arrow_end.x, arrow_end.y) {
+        (x1, y1, x2, y2) if x1 < x2 && y1 < y2 => Some(SignedPoint { x: x1, y: y2 }),
+        (x1, y1, x2, y2) if x1 > x2 && y1 > y2 => Some(SignedPoint { x: x2, y: y1 }),
+        (x1, y1, x2, y2) if x1 > x2 && y1 < y2 => Some(SignedPoint { x: x1, y: y2 }),
+        (x1, y1, x2, y2) if x1 < x2 && y1 > y2 => Some(SignedPoint { x: x2, y: y1 }),
+        (x1, y1, x2, y2) if x1 == x2 && y1 < y2 => Some(SignedPoint { x: x1, y: y1 + 1 }),
+        (x1, y1, x2, y2) if x1 == x2 && y1 > y2 => Some(SignedPoint { x: x1, y: y2 + 1 }),
+        (x1, y1, x2, y2) if x1 < x2 && y1 == y2 => Some(SignedPoint { x: x1 + 1, y: y1 }),
+        (x1, y1, x2, y2) if x1 > x2 && y1 == y2 => Some(SignedPoint { x: x2 + 1, y: y1 }),
+        _ => None,
+    }
+}
 
-        let arrow_middle = match (arrow_start.x, arrow_start.y, arrow_end.x, arrow_end.y) {
-            (x1, y1, x2, y2) if x1 < x2 && y1 < y2 => domain::Point { x: x1, y: y2 },
-            (x1, y1, x2, y2) if x1 > x2 && y1 > y2 => domain::Point { x: x2, y: y1 },
-            (x1, y1, x2, y2) if x1 > x2 && y1 < y2 => domain::Point { x: x1, y: y2 },
-            (x1, y1, x2, y2) if x1 < x2 && y1 > y2 => domain::Point { x: x2, y: y1 },
-            (x1, y1, x2, y2) if x1 == x2 && y1 < y2 => domain::Point { x: x1, y: y1 + 1 },
-            (x1, y1, x2, y2) if x1 == x2 && y1 > y2 => domain::Point { x: x1, y: y2 + 1 },
-            (x1, y1, x2, y2) if x1 < x2 && y1 == y2 => domain::Point { x: x1 + 1, y: y1 },
-            (x1, y1, x2, y2) if x1 > x2 && y1 == y2 => domain::Point { x: x2 + 1, y: y1 },
-            _ => continue,
-        };
+/// The way a step from one point to another on the same row or column goes.
+fn get_heading(from: &SignedPoint, to: &SignedPoint) -> Direction {
+    match (to.x.cmp(&from.x), to.y.cmp(&from.y)) {
+        (std::cmp::Ordering::Less, _) => Direction::Left,
+        (std::cmp::Ordering::Greater, _) => Direction::Right,
+        (_, std::cmp::Ordering::Less) => Direction::Top,
+        _ => Direction::Bottom,
+    }
+}
+
+/// Facing sides which do not line up get two bends halfway between them, so
+/// the arrow leaves and enters both boxes straight instead of running along
+/// a border. Other arrows take one bend.
+fn get_default_points(
+    arrow_start: &SignedPoint,
+    arrow_end: &SignedPoint,
+    directions: (Direction, Direction),
+) -> Option<Vec<SignedPoint>> {
+    let mut points = match directions {
+        (Direction::Bottom, Direction::Top) | (Direction::Top, Direction::Bottom)
+            if arrow_start.x != arrow_end.x =>
+        {
+            let y = (arrow_start.y + arrow_end.y) / 2;
+            vec![
+                arrow_start.clone(),
+                SignedPoint {
+                    x: arrow_start.x,
+                    y,
+                },
+                SignedPoint { x: arrow_end.x, y },
+                arrow_end.clone(),
+            ]
+        }
+        (Direction::Right, Direction::Left) | (Direction::Left, Direction::Right)
+            if arrow_start.y != arrow_end.y =>
+        {
+            let x = (arrow_start.x + arrow_end.x) / 2;
+            vec![
+                arrow_start.clone(),
+                SignedPoint {
+                    x,
+                    y: arrow_start.y,
+                },
+                SignedPoint { x, y: arrow_end.y },
+                arrow_end.clone(),
+            ]
+        }
+        _ => vec![
+            arrow_start.clone(),
+            get_default_middle(arrow_start, arrow_end)?,
+            arrow_end.clone(),
+        ],
+    };
+    points.dedup();
+    // Boxes too close for the bends leave no room to run straight out.
+    let last = points.len().checked_sub(1).filter(|last| *last > 0)?;
+    let is_straight = get_heading(&points[0], &points[1]) == directions.0
+        && get_heading(&points[last], &points[last - 1]) == directions.1;
+    is_straight.then_some(points)
+}
+
+const ALL_DIRECTIONS: [Direction; 4] = [
+    Direction::Top,
+    Direction::Bottom,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// The next free anchor on each of the given sides which has one left, or on
+/// all of them once they are full.
+fn get_route_candidates(
+    node: &ArrowAnchorsForNode,
+    directions: &[Direction],
+) -> Vec<(SignedPoint, Direction)> {
+    let free_directions = directions
+        .iter()
+        .copied()
+        .filter(|direction| node.has_free_anchor(*direction))
+        .collect::<Vec<_>>();
+    let directions = if free_directions.is_empty() {
+        directions
+    } else {
+        &free_directions
+    };
+    directions
+        .iter()
+        .map(|direction| (node.peek_arrow_anchor(*direction), *direction))
+        .collect()
+}
 
+fn take_route_anchor(
+    node: &mut ArrowAnchorsForNode,
+    candidates: &[(SignedPoint, Direction)],
+    point: &SignedPoint,
+) {
+    let direction = candidates
+        .iter()
+        .find(|(anchor, _)| anchor == point)
+        .map(|(_, direction)| *direction);
+    if let Some(direction) = direction {
+        node.get_arrow_anchor(direction);
+    }
+}
+
+/// Picks sides from where the boxes lie after scaling. Boxes apart on both
+/// axes get an L-shape around the corner, boxes apart on one axis face each
+/// other, and boxes too close for either are left to the router.
+fn get_default_directions(
+    node_from: &ArrowAnchorsForNode,
+    node_to: &ArrowAnchorsForNode,
+) -> Option<(Direction, Direction)> {
+    let from_right = node_from.anchor.x + node_from.width;
+    let to_right = node_to.anchor.x + node_to.width;
+    let from_bottom = node_from.anchor.y + node_from.height;
+    let to_bottom = node_to.anchor.y + node_to.height;
+    let to_is_right = from_right + 1 < node_to.anchor.x;
+    let to_is_left = to_right + 1 < node_from.anchor.x;
+    let to_is_below = from_bottom + 1 < node_to.anchor.y;
+    let to_is_above = to_bottom + 1 < node_from.anchor.y;
+    match (to_is_right, to_is_left, to_is_below, to_is_above) {
+        (true, _, true, _) => Some((Direction::Bottom, Direction::Left)),
+        (_, true, _, true) => Some((Direction::Left, Direction::Bottom)),
+        (_, true, true, _) => Some((Direction::Bottom, Direction::Right)),
+        (true, _, _, true) => Some((Direction::Right, Direction::Bottom)),
+        (true, _, _, _) => Some((Direction::Right, Direction::Left)),
+        (_, true, _, _) => Some((Direction::Left, Direction::Right)),
+        (_, _, true, _) => Some((Direction::Bottom, Direction::Top)),
+        (_, _, _, true) => Some((Direction::Top, Direction::Bottom)),
+        _ => None,
+    }
+}
+
+/// Uses the usual L-shape between the default sides when it stays clear of
+/// boxes, otherwise routes from every side of both nodes. When no route is
+/// found the L-shape is kept anyway, moved to other sides where the default
+/// ones are full, and reported if it runs through a box. Anchors are only taken for the sides the arrow ends up
+/// using.
+fn get_arrow_points(
+    router: &mut router::Router,
+    node_from: &mut ArrowAnchorsForNode,
+    node_to: &mut ArrowAnchorsForNode,
+    layout_direction: Option<layout::LayoutDirection>,
+) -> Option<Vec<SignedPoint>> {
+    let preferred_directions = get_preferred_directions(node_from, node_to, layout_direction);
+    let directions = preferred_directions.or_else(|| get_default_directions(node_from, node_to));
+    if let Some((start_direction, end_direction)) = directions {
+        let arrow_start = node_from.peek_arrow_anchor(start_direction);
+        let arrow_end = node_to.peek_arrow_anchor(end_direction);
+        if let Some(points) =
+            get_default_points(&arrow_start, &arrow_end, (start_direction, end_direction))
+        {
+            if node_from.has_free_anchor(start_direction)
+                && node_to.has_free_anchor(end_direction)
+                && router.is_clear(&points.iter().collect::<Vec<_>>())
+            {
+                node_from.get_arrow_anchor(start_direction);
+                node_to.get_arrow_anchor(end_direction);
+                return Some(points);
+            }
+        }
+    }
+
+    let starts = get_route_candidates(node_from, &ALL_DIRECTIONS);
+    let ends = get_route_candidates(node_to, &ALL_DIRECTIONS);
+    if let Some(route) = router.route(&starts, &ends) {
+        take_route_anchor(node_from, &starts, &route[0]);
+        take_route_anchor(node_to, &ends, &route[route.len() - 1]);
+        return Some(route);
+    }
+    let (start_direction, end_direction) = directions?;
+    let start_direction = node_from.get_free_direction(start_direction);
+    let end_direction = node_to.get_free_direction(end_direction);
+    let points = get_default_points(
+        &node_from.peek_arrow_anchor(start_direction),
+        &node_to.peek_arrow_anchor(end_direction),
+        (start_direction, end_direction),
+    )?;
+    node_from.get_arrow_anchor(start_direction);
+    node_to.get_arrow_anchor(end_direction);
+    Some(points)
+}
+
+/// Loops leave the right side of the box and come back through its top, or
+/// through its bottom when there is no room above.
+fn get_loop_points(
+    router: &mut router::Router,
+    node: &mut ArrowAnchorsForNode,
+) -> Option<Vec<SignedPoint>> {
+    let starts = get_route_candidates(node, &[Direction::Right]);
+    let ends = get_route_candidates(node, &[Direction::Top, Direction::Bottom]);
+    let route = router.route(&starts, &ends)?;
+    take_route_anchor(node, &starts, &route[0]);
+    take_route_anchor(node, &ends, &route[route.len() - 1]);
+    Some(route)
+}
+
+fn get_pair_mut<T>(items: &mut [T], first: usize, second: usize) -> (&mut T, &mut T) {
+    if first < second {
+        let (left, right) = items.split_at_mut(second);
+        (&mut left[first], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(first);
+        (&mut right[0], &mut left[second])
+    }
+}
+
+/// Moves nodes and arrows right and down so nothing lies left of or above
+/// the origin.
+fn include_normalised_arrows(
+    map: &mut domain::Map,
+    arrows: Vec<(&InnerMapping, Vec<SignedPoint>)>,
+) {
+    let points = arrows.iter().flat_map(|(_, points)| points);
+    let shift_x = -points.clone().map(|point| point.x).fold(0, isize::min);
+    let shift_y = -points.map(|point| point.y).fold(0, isize::min);
+    let get_point = |x: isize, y: isize| domain::Point {
+        x: (x + shift_x) as usize,
+        y: (y + shift_y) as usize,
+    };
+
+    map.nodes = std::mem::take(&mut map.nodes)
+        .into_iter()
+        .map(|(anchor, node)| (get_point(anchor.x as isize, anchor.y as isize), node))
+        .collect();
+    for (mapping, points) in arrows {
         map.arrows.insert(domain::Arrow {
-            start: arrow_start,
-            middle: arrow_middle,
-            end: arrow_end,
+            from: mapping.source.to_owned(),
+            to: mapping.target.to_owned(),
+            line: mapping.line,
+            points: points
+                .iter()
+                .map(|point| get_point(point.x, point.y))
+                .collect(),
             body: read_arrow_body(&mapping.arrow),
             head: read_arrow_head(&mapping.arrow),
         });
     }
 }
 
+/// Returns how many arrows every side of every node got.
+fn include_arrows(
+    map: &mut domain::Map,
+    inner_mappings: &Vec<&InnerMapping>,
+    layout_direction: Option<layout::LayoutDirection>,
+    side_counts: &HashMap<String, SideCounts>,
+) -> HashMap<String, SideCounts> {
+    let (width, height) = map.get_size();
+    let mut router = router::Router::new(
+        SignedPoint {
+            x: -(ROUTING_MARGIN as isize),
+            y: -(ROUTING_MARGIN as isize),
+        },
+        width + 2 * ROUTING_MARGIN,
+        height + 2 * ROUTING_MARGIN,
+    );
+    for (anchor, node) in map.nodes.iter() {
+        router.block(
+            &SignedPoint::from_point(anchor),
+            node.get_width(),
+            node.get_height(),
+        );
+    }
+
+    let mut anchors_for_nodes = vec![];
+    for (anchor, node) in map.nodes.iter() {
+        anchors_for_nodes.push(ArrowAnchorsForNode::new(node, anchor));
+    }
+    for node in anchors_for_nodes.iter_mut() {
+        node.side_counts = side_counts
+            .get(node.node_label)
+            .cloned()
+            .unwrap_or_default();
+    }
+    let indices = anchors_for_nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.node_label, index))
+        .collect::<HashMap<_, _>>();
+    let mut arrows = Vec::new();
+    for mapping in inner_mappings {
+        let from_index = indices.get(mapping.source.as_str()).copied();
+        let to_index = indices.get(mapping.target.as_str()).copied();
+        let points = match (from_index, to_index) {
+            (Some(from_index), Some(to_index)) if from_index == to_index => {
+                get_loop_points(&mut router, &mut anchors_for_nodes[from_index])
+            }
+            (Some(from_index), Some(to_index)) => {
+                let (node_from, node_to) =
+                    get_pair_mut(&mut anchors_for_nodes, from_index, to_index);
+                get_arrow_points(&mut router, node_from, node_to, layout_direction)
+            }
+            _ => None,
+        };
+        let Some(points) = points else {
+            map.diagnostics.push(format!(
+                "Line {}: cannot draw the arrow from {} to {}",
+                mapping.line, mapping.source, mapping.target
+            ));
+            continue;
+        };
+        let path = points.iter().collect::<Vec<_>>();
+        if router.crosses_box(&path) {
+            map.diagnostics.push(format!(
+                "Line {}: the arrow from {} to {} crosses a box",
+                mapping.line, mapping.source, mapping.target
+            ));
+        }
+        router.add_path(&path);
+        // Keeps later arrows off the cells where this one starts and ends.
+        router.reserve(&points[0]);
+        router.reserve(&points[points.len() - 1]);
+        arrows.push((*mapping, points));
+    }
+    let used_counts = anchors_for_nodes
+        .iter()
+        .map(|node| (node.node_label.to_owned(), node.get_used_counts()))
+        .collect();
+    include_normalised_arrows(map, arrows);
+    used_counts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,30 +1148,23 @@ mod tests {
     #[test]
     fn get_map_example01() {
         let input = "A -> B";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
-        nodes.insert(
-            domain::Point { x: 0, y: 0 },
-            domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 20, y: 0 },
-            domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 5, y: 1 },
-            middle: domain::Point { x: 6, y: 1 },
-            end: domain::Point { x: 19, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 0 }, domain::Node::new("A", 1));
+        nodes.insert(domain::Point { x: 20, y: 0 }, domain::Node::new("B", 1));
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 5, y: 1 },
+            domain::Point { x: 6, y: 1 },
+            domain::Point { x: 19, y: 1 },
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -301,48 +1186,111 @@ mod tests {
     }
 
     #[test]
-    fn get_map_example02() {
+    fn get_map_error_unknown_direction() {
+        let input = "direction XY\nA -> B";
+        let result = read_input(input);
+        assert_eq!(Some("Unknown direction: XY".to_owned()), result.err());
+    }
+
+    #[test]
+    fn get_map_with_direction_keeps_line_numbers() {
+        let input = "direction TB\nA -> B";
+        let result = read_input(input).unwrap();
+        let arrow = result.arrows.iter().next().unwrap();
+        assert_eq!(2, arrow.line);
+        assert!(result.nodes.values().all(|node| node.line == 2));
+    }
+
+    #[test]
+    fn get_constraints_from_hint_lines() {
         let input = "\
 A -> B
-B -> C";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
-        nodes.insert(
-            domain::Point { x: 0, y: 0 },
-            domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
+B -> C
+A @ (1, 2)
+A right-of B
+B above C
+rank same: A C
+align: A B C";
+        let inner_lines = get_inner_lines(input)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let inner_constraints = inner_lines
+            .iter()
+            .filter_map(|line| match line {
+                InnerLine::Constraint(constraint) => Some(constraint),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let nodes = ["A", "B", "C"].map(String::from);
+
+        assert_eq!(
+            get_constraints(&inner_constraints, &nodes),
+            Ok(vec![
+                layout::LayoutConstraint::Pin {
+                    node: 0,
+                    x: 1,
+                    y: 2
+                },
+                layout::LayoutConstraint::LeftOf(1, 0),
+                layout::LayoutConstraint::Above(1, 2),
+                layout::LayoutConstraint::SameRank(vec![0, 2]),
+                layout::LayoutConstraint::Aligned(vec![0, 1, 2]),
+            ])
         );
-        nodes.insert(
-            domain::Point { x: 24, y: 0 },
-            domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
-            },
+    }
+
+    #[test]
+    fn get_map_rejects_bad_hints() {
+        assert_eq!(
+            read_input("A -> B\nA @ (1)").unwrap_err(),
+            "Cannot understand this line: A @ (1)"
         );
-        nodes.insert(
-            domain::Point { x: 48, y: 0 },
-            domain::Node {
-                name: "C".to_owned(),
-                border: domain::BorderType::Box,
-            },
+        assert_eq!(
+            read_input("A -> B\nA left-of C").unwrap_err(),
+            "Line 2: unknown node C"
         );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 5, y: 1 },
-            middle: domain::Point { x: 6, y: 1 },
-            end: domain::Point { x: 23, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 29, y: 1 },
-            middle: domain::Point { x: 30, y: 1 },
-            end: domain::Point { x: 47, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        assert_eq!(
+            read_input("A -> B\nrank same: A").unwrap_err(),
+            "Cannot understand this line: rank same: A"
+        );
+        assert_eq!(
+            read_input("A -> B\nalign: A").unwrap_err(),
+            "Cannot understand this line: align: A"
+        );
+    }
+
+    #[test]
+    fn get_map_example02() {
+        let input = "\
+A -> B
+B -> C";
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 0 }, domain::Node::new("A", 1));
+        nodes.insert(domain::Point { x: 24, y: 0 }, domain::Node::new("B", 1));
+        nodes.insert(domain::Point { x: 48, y: 0 }, domain::Node::new("C", 2));
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 5, y: 1 },
+            domain::Point { x: 6, y: 1 },
+            domain::Point { x: 23, y: 1 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "B",
+            "C",
+            2,
+            domain::Point { x: 29, y: 1 },
+            domain::Point { x: 30, y: 1 },
+            domain::Point { x: 47, y: 1 },
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -358,51 +1306,40 @@ B -> C";
 A -> B
 B -> C
 C -> A";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
-        nodes.insert(
-            domain::Point { x: 0, y: 0 },
-            domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 12, y: 5 },
-            domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 24, y: 0 },
-            domain::Node {
-                name: "C".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 2, y: 3 },
-            middle: domain::Point { x: 2, y: 6 },
-            end: domain::Point { x: 11, y: 6 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 17, y: 6 },
-            middle: domain::Point { x: 26, y: 6 },
-            end: domain::Point { x: 26, y: 3 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 23, y: 1 },
-            middle: domain::Point { x: 6, y: 1 },
-            end: domain::Point { x: 5, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 0 }, domain::Node::new("A", 1));
+        nodes.insert(domain::Point { x: 12, y: 5 }, domain::Node::new("B", 1));
+        nodes.insert(domain::Point { x: 24, y: 0 }, domain::Node::new("C", 2));
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 2, y: 3 },
+            domain::Point { x: 2, y: 6 },
+            domain::Point { x: 11, y: 6 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "B",
+            "C",
+            2,
+            domain::Point { x: 17, y: 6 },
+            domain::Point { x: 26, y: 6 },
+            domain::Point { x: 26, y: 3 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "C",
+            "A",
+            3,
+            domain::Point { x: 23, y: 1 },
+            domain::Point { x: 6, y: 1 },
+            domain::Point { x: 5, y: 1 },
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -417,37 +1354,43 @@ C -> A";
         let input = "\
 A -> B
 B -> A";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
         nodes.insert(
             domain::Point { x: 0, y: 0 },
             domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("A", 1)
             },
         );
         nodes.insert(
             domain::Point { x: 20, y: 0 },
             domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("B", 1)
             },
         );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 5, y: 1 },
-            middle: domain::Point { x: 6, y: 1 },
-            end: domain::Point { x: 19, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 19, y: 2 },
-            middle: domain::Point { x: 6, y: 2 },
-            end: domain::Point { x: 5, y: 2 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 5, y: 1 },
+            domain::Point { x: 6, y: 1 },
+            domain::Point { x: 19, y: 1 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "B",
+            "A",
+            2,
+            domain::Point { x: 19, y: 2 },
+            domain::Point { x: 6, y: 2 },
+            domain::Point { x: 5, y: 2 },
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -465,72 +1408,49 @@ B -> C
 C -> D
 D -> A
 D -> B";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
-        nodes.insert(
-            domain::Point { x: 0, y: 1 },
-            domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
         nodes.insert(
-            domain::Point { x: 20, y: 0 },
+            domain::Point { x: 0, y: 3 },
             domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("A", 1)
             },
         );
+        nodes.insert(domain::Point { x: 16, y: 7 }, domain::Node::new("D", 3));
+        nodes.insert(domain::Point { x: 20, y: 2 }, domain::Node::new("B", 1));
         nodes.insert(
-            domain::Point { x: 36, y: 3 },
+            domain::Point { x: 36, y: 5 },
             domain::Node {
-                name: "C".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("C", 2)
             },
         );
-        nodes.insert(
-            domain::Point { x: 16, y: 5 },
-            domain::Node {
-                name: "D".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 15, y: 6 },
-            middle: domain::Point { x: 2, y: 6 },
-            end: domain::Point { x: 2, y: 4 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 21, y: 7 },
-            middle: domain::Point { x: 24, y: 7 },
-            end: domain::Point { x: 24, y: 3 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 5, y: 2 },
-            middle: domain::Point { x: 5, y: 3 },
-            end: domain::Point { x: 22, y: 3 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 23, y: 3 },
-            middle: domain::Point { x: 23, y: 4 },
-            end: domain::Point { x: 35, y: 4 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 38, y: 6 },
-            middle: domain::Point { x: 22, y: 6 },
-            end: domain::Point { x: 21, y: 6 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(get_arrow("A", "B", 1, &[(5, 4), (12, 4), (12, 3), (19, 3)]));
+        arrows.insert(get_arrow(
+            "B",
+            "C",
+            2,
+            &[(25, 3), (30, 3), (30, 6), (35, 6)],
+        ));
+        arrows.insert(get_arrow(
+            "C",
+            "D",
+            3,
+            &[(35, 7), (28, 7), (28, 8), (21, 8)],
+        ));
+        arrows.insert(get_arrow("D", "A", 4, &[(15, 8), (10, 8), (10, 5), (5, 5)]));
+        arrows.insert(get_arrow(
+            "D",
+            "B",
+            5,
+            &[(18, 6), (18, 0), (22, 0), (22, 1)],
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -548,86 +1468,52 @@ B -> C
 C -> D
 B -> E
 E -> F";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
-        nodes.insert(
-            domain::Point { x: 0, y: 3 },
-            domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 24, y: 5 },
-            domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 40, y: 0 },
-            domain::Node {
-                name: "C".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 64, y: 0 },
-            domain::Node {
-                name: "D".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 36, y: 10 },
-            domain::Node {
-                name: "E".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 52, y: 15 },
-            domain::Node {
-                name: "F".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 2, y: 6 },
-            middle: domain::Point { x: 3, y: 6 },
-            end: domain::Point { x: 23, y: 6 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 29, y: 6 },
-            middle: domain::Point { x: 42, y: 6 },
-            end: domain::Point { x: 42, y: 3 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 45, y: 1 },
-            middle: domain::Point { x: 46, y: 1 },
-            end: domain::Point { x: 63, y: 1 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 26, y: 8 },
-            middle: domain::Point { x: 26, y: 11 },
-            end: domain::Point { x: 35, y: 11 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 38, y: 13 },
-            middle: domain::Point { x: 38, y: 16 },
-            end: domain::Point { x: 51, y: 16 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 3 }, domain::Node::new("A", 1));
+        nodes.insert(domain::Point { x: 24, y: 5 }, domain::Node::new("B", 1));
+        nodes.insert(domain::Point { x: 36, y: 10 }, domain::Node::new("E", 4));
+        nodes.insert(domain::Point { x: 40, y: 0 }, domain::Node::new("C", 2));
+        nodes.insert(domain::Point { x: 52, y: 15 }, domain::Node::new("F", 5));
+        nodes.insert(domain::Point { x: 64, y: 0 }, domain::Node::new("D", 3));
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(get_arrow("A", "B", 1, &[(5, 4), (14, 4), (14, 6), (23, 6)]));
+        arrows.insert(domain::Arrow::new(
+            "B",
+            "C",
+            2,
+            domain::Point { x: 29, y: 6 },
+            domain::Point { x: 42, y: 6 },
+            domain::Point { x: 42, y: 3 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "B",
+            "E",
+            4,
+            domain::Point { x: 26, y: 8 },
+            domain::Point { x: 26, y: 11 },
+            domain::Point { x: 35, y: 11 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "C",
+            "D",
+            3,
+            domain::Point { x: 45, y: 1 },
+            domain::Point { x: 46, y: 1 },
+            domain::Point { x: 63, y: 1 },
+        ));
+        arrows.insert(domain::Arrow::new(
+            "E",
+            "F",
+            5,
+            domain::Point { x: 38, y: 13 },
+            domain::Point { x: 38, y: 16 },
+            domain::Point { x: 51, y: 16 },
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -644,65 +1530,55 @@ A -> B
 B -> C
 C -> A
 A -> D";
-        let mut nodes: HashMap<domain::Point, domain::Node> = HashMap::new();
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 9 }, domain::Node::new("D", 4));
         nodes.insert(
             domain::Point { x: 16, y: 5 },
             domain::Node {
-                name: "A".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 24, y: 0 },
-            domain::Node {
-                name: "B".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("A", 1)
             },
         );
+        nodes.insert(domain::Point { x: 24, y: 0 }, domain::Node::new("B", 1));
         nodes.insert(
             domain::Point { x: 36, y: 4 },
             domain::Node {
-                name: "C".to_owned(),
-                border: domain::BorderType::Box,
-            },
-        );
-        nodes.insert(
-            domain::Point { x: 0, y: 9 },
-            domain::Node {
-                name: "D".to_owned(),
-                border: domain::BorderType::Box,
+                height: 4,
+                ..domain::Node::new("C", 2)
             },
         );
-        let mut arrows: HashSet<domain::Arrow> = HashSet::new();
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 21, y: 6 },
-            middle: domain::Point { x: 26, y: 6 },
-            end: domain::Point { x: 26, y: 3 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 27, y: 3 },
-            middle: domain::Point { x: 27, y: 5 },
-            end: domain::Point { x: 35, y: 5 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 38, y: 7 },
-            middle: domain::Point { x: 22, y: 7 },
-            end: domain::Point { x: 21, y: 7 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        arrows.insert(domain::Arrow {
-            start: domain::Point { x: 18, y: 8 },
-            middle: domain::Point { x: 18, y: 10 },
-            end: domain::Point { x: 5, y: 10 },
-            body: domain::ArrowBody::Basic,
-            head: domain::ArrowHead::Basic,
-        });
-        let expected = domain::Map { nodes, arrows };
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 21, y: 6 },
+            domain::Point { x: 26, y: 6 },
+            domain::Point { x: 26, y: 3 },
+        ));
+        arrows.insert(get_arrow(
+            "A",
+            "D",
+            4,
+            &[(15, 7), (10, 7), (10, 10), (5, 10)],
+        ));
+        arrows.insert(get_arrow(
+            "B",
+            "C",
+            2,
+            &[(29, 1), (32, 1), (32, 5), (35, 5)],
+        ));
+        arrows.insert(get_arrow(
+            "C",
+            "A",
+            3,
+            &[(35, 6), (28, 6), (28, 7), (21, 7)],
+        ));
+        let expected = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
 
         let result = read_input(input);
         if let Ok(mapped_result) = result {
@@ -712,6 +1588,440 @@ A -> D";
         }
     }
 
+    #[test]
+    fn include_nodes_separates_long_and_colliding_labels() {
+        let approximation = ["A very long label", "B", "C"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| layout::NodeApproximation {
+                name: name.to_string(),
+                position: layout::PointApproximation {
+                    x: index.min(1),
+                    y: 0,
+                },
+            })
+            .collect::<Vec<_>>();
+        let mut map = domain::Map {
+            nodes: BTreeMap::new(),
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        include_nodes(
+            &mut map,
+            &approximation,
+            &[],
+            None,
+            DEFAULT_SCALE,
+            None,
+            &HashSet::new(),
+        );
+
+        assert_eq!(map.nodes.len(), 3);
+        let boxes = map.nodes.iter().collect::<Vec<_>>();
+        for (index, (anchor, node)) in boxes.iter().enumerate() {
+            for (other_anchor, other_node) in &boxes[index + 1..] {
+                assert!(!domain::boxes_overlap(
+                    anchor,
+                    (node.get_width(), node.get_height()),
+                    other_anchor,
+                    (other_node.get_width(), other_node.get_height()),
+                    MIN_GAP,
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn include_arrows_routes_around_boxes() {
+        let mut map = get_map_with_nodes(&[("A", 0, 0), ("B", 0, 6), ("C", 20, 8)]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "C")],
+            None,
+            &HashMap::new(),
+        );
+
+        let arrow = map.arrows.iter().next().unwrap();
+        assert_eq!(
+            arrow.points,
+            vec![
+                domain::Point { x: 5, y: 1 },
+                domain::Point { x: 22, y: 1 },
+                domain::Point { x: 22, y: 7 },
+            ]
+        );
+    }
+
+    #[test]
+    fn include_arrows_routes_with_several_bends() {
+        let mut map = get_map_with_nodes(&[("A", 0, 0), ("B", 10, 0), ("C", 20, 0)]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "C")],
+            None,
+            &HashMap::new(),
+        );
+
+        let arrow = map.arrows.iter().next().unwrap();
+        assert!(arrow.points.len() > 3);
+        let mut router = router::Router::new(SignedPoint { x: 0, y: 0 }, 30, 10);
+        for (anchor, node) in &map.nodes {
+            router.block(
+                &SignedPoint::from_point(anchor),
+                node.get_width(),
+                node.get_height(),
+            );
+        }
+        let points = arrow
+            .points
+            .iter()
+            .map(SignedPoint::from_point)
+            .collect::<Vec<_>>();
+        assert!(router.is_clear(&points.iter().collect::<Vec<_>>()));
+    }
+
+    fn get_map_with_nodes(nodes: &[(&str, usize, usize)]) -> domain::Map {
+        let mut map = domain::Map {
+            nodes: BTreeMap::new(),
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        for (name, x, y) in nodes {
+            map.nodes
+                .insert(domain::Point { x: *x, y: *y }, domain::Node::new(name, 1));
+        }
+        map
+    }
+
+    fn get_mapping(source: &str, target: &str) -> InnerMapping {
+        InnerMapping {
+            source: source.to_owned(),
+            arrow: "->".to_owned(),
+            target: target.to_owned(),
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn get_map_draws_loops() {
+        let map = read_input("A -> A\nA -> B").unwrap();
+        assert_eq!(map.arrows.len(), 2);
+        assert!(map.diagnostics.is_empty());
+    }
+
+    fn get_chain_map(max_width: Option<usize>, hint: &str) -> (domain::Map, layout::LayoutCache) {
+        let input = format!(
+            "direction LR\nAlpha -> Beta\nBeta -> Gamma\nGamma -> Delta\nDelta -> Epsilon\n{}",
+            hint
+        );
+        let options = options::Options {
+            layout: Box::new(layout::LayeredLayout),
+            max_width,
+            ..options::Options::default()
+        };
+        read_input_with_options(&input, &options).unwrap()
+    }
+
+    #[test]
+    fn get_map_fits_the_maximum_width() {
+        let (wide, _) = get_chain_map(None, "");
+        let (width, height) = wide.get_size();
+        assert!(width > 60 && height == 3);
+
+        let (compact, _) = get_chain_map(Some(60), "");
+        let (width, height) = compact.get_size();
+        assert!(width <= 60 && height == 3);
+
+        let (turned, _) = get_chain_map(Some(20), "");
+        assert!(turned.get_size().0 <= 20);
+        assert_eq!(
+            get_anchor(&turned, "Alpha").x,
+            get_anchor(&turned, "Beta").x
+        );
+    }
+
+    #[test]
+    fn get_map_draws_cached_layouts_the_same_way() {
+        let (first, layout_cache) = get_chain_map(Some(20), "");
+        let input = "direction LR\nAlpha -> Beta\nBeta -> Gamma\nGamma -> Delta\nDelta -> Epsilon";
+        for _ in 0..2 {
+            let options = options::Options {
+                layout: Box::new(layout::LayeredLayout),
+                max_width: Some(20),
+                previous_layout: Some(layout_cache.clone()),
+                ..options::Options::default()
+            };
+            let (cached, next_cache) = read_input_with_options(input, &options).unwrap();
+            assert_eq!(cached, first);
+            assert_eq!(next_cache, layout_cache);
+        }
+    }
+
+    #[test]
+    fn get_map_keeps_the_start_with_the_best_drawing() {
+        let input = "N0 -> N1\nN1 -> N2\nN2 -> N3\nN3 -> N0\nN0 -> N4\nN4 -> N5\n\
+                     N5 -> N6\nN6 -> N7\nN7 -> N2";
+        let get_score = |starts| {
+            let options = options::Options {
+                layout: Box::new(layout::ForceDirectedLayout {
+                    seed: 1,
+                    starts,
+                    ..layout::ForceDirectedLayout::default()
+                }),
+                ..options::Options::default()
+            };
+            let (map, _) = read_input_with_options(input, &options).unwrap();
+            stats::get_stats(&map).get_score()
+        };
+        assert!(get_score(5) < get_score(1));
+    }
+
+    #[test]
+    fn get_map_keeps_hinted_layouts_upright() {
+        let (map, _) = get_chain_map(Some(20), "Alpha left-of Beta");
+        assert_eq!(get_anchor(&map, "Alpha").y, get_anchor(&map, "Beta").y);
+    }
+
+    fn get_layered_map(input: &str, previous_layout: Option<&str>) -> domain::Map {
+        let options = options::Options {
+            layout: Box::new(layout::LayeredLayout),
+            previous_layout: previous_layout.map(|text| layout::LayoutCache::parse(text).unwrap()),
+            ..options::Options::default()
+        };
+        read_input_with_options(input, &options).unwrap().0
+    }
+
+    fn get_anchor<'a>(map: &'a domain::Map, name: &str) -> &'a domain::Point {
+        map.nodes
+            .iter()
+            .find(|(_, node)| node.name == name)
+            .map(|(anchor, _)| anchor)
+            .unwrap()
+    }
+
+    #[test]
+    fn get_map_keeps_pinned_nodes_in_place_with_the_layered_layout() {
+        let map = get_layered_map("A -> B\nB @ (0, 0)", None);
+        assert_eq!(*get_anchor(&map, "B"), domain::Point { x: 0, y: 0 });
+        assert_ne!(*get_anchor(&map, "A"), domain::Point { x: 0, y: 0 });
+
+        let map = get_layered_map("A -> B\nB @ (0, 0)", Some("0 0 A\n0 0 B"));
+        assert_eq!(*get_anchor(&map, "B"), domain::Point { x: 0, y: 0 });
+        assert!(map.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn get_map_reports_pins_on_the_same_cell() {
+        let map = get_layered_map("A -> B\nA @ (1, 0)\nB @ (1, 0)", None);
+        assert_eq!(
+            map.diagnostics,
+            vec!["Line 3: B is pinned to the same cell as A".to_owned()]
+        );
+        assert_eq!(map.nodes.len(), 2);
+    }
+
+    /// Whether one more step past `to`, coming from `from`, lands in the box.
+    fn steps_into(
+        node: (&domain::Point, &domain::Node),
+        from: &domain::Point,
+        to: &domain::Point,
+    ) -> bool {
+        let (anchor, node) = node;
+        let (from, to) = (SignedPoint::from_point(from), SignedPoint::from_point(to));
+        let x = to.x + (to.x - from.x).signum();
+        let y = to.y + (to.y - from.y).signum();
+        (anchor.x as isize..(anchor.x + node.get_width()) as isize).contains(&x)
+            && (anchor.y as isize..(anchor.y + node.get_height()) as isize).contains(&y)
+    }
+
+    #[test]
+    fn get_map_attaches_arrows_straight_after_scaling() {
+        let input = "A -> B\nB -> C\nC -> D\nD -> E\nE -> F\nF -> G";
+        let layouts: [Box<dyn layout::LayoutEngine>; 2] = [
+            Box::new(layout::LayeredLayout),
+            Box::new(layout::ForceDirectedLayout::default()),
+        ];
+        for layout in layouts {
+            let options = options::Options {
+                layout,
+                max_width: Some(40),
+                max_height: Some(5),
+                ..options::Options::default()
+            };
+            let (map, _) = read_input_with_options(input, &options).unwrap();
+
+            assert_eq!(map.arrows.len(), 6);
+            let boxes = map
+                .nodes
+                .iter()
+                .map(|(anchor, node)| (node.name.as_str(), (anchor, node)))
+                .collect::<HashMap<_, _>>();
+            for arrow in &map.arrows {
+                let (points, last) = (&arrow.points, arrow.points.len() - 1);
+                assert!(steps_into(
+                    boxes[arrow.to.as_str()],
+                    &points[last - 1],
+                    &points[last]
+                ));
+                assert!(steps_into(
+                    boxes[arrow.from.as_str()],
+                    &points[1],
+                    &points[0]
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn get_map_grows_boxes_with_many_arrows() {
+        let input = "A -> H\nB -> H\nC -> H\nD -> H\nE -> H\nF -> H";
+        let options = options::Options {
+            layout: Box::new(layout::LayeredLayout),
+            ..options::Options::default()
+        };
+        let (map, _) = read_input_with_options(input, &options).unwrap();
+
+        let (anchor, hub) = map.nodes.iter().find(|(_, node)| node.name == "H").unwrap();
+        let mut side_counts = HashMap::new();
+        for end in map.arrows.iter().map(|arrow| arrow.get_end()) {
+            let (side, length) = match (end.x, end.y) {
+                (_, y) if y + 1 == anchor.y => (Direction::Top, hub.get_width() - 2),
+                (_, y) if y == anchor.y + hub.get_height() => {
+                    (Direction::Bottom, hub.get_width() - 2)
+                }
+                (x, _) if x + 1 == anchor.x => (Direction::Left, hub.get_height() - 2),
+                _ => (Direction::Right, hub.get_height() - 2),
+            };
+            let count = side_counts.entry(side).or_insert(0);
+            *count += 1;
+            assert!(*count <= length);
+        }
+        let ends = map
+            .arrows
+            .iter()
+            .map(|arrow| arrow.get_end())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(ends.len(), 6);
+        assert!(map.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn get_map_gives_every_arrow_on_a_hub_its_own_cells() {
+        let input = "Hub -> A\nHub -> B\nHub -> C\nHub -> D\nHub -> E\nHub -> F\n\
+                     G -> Hub\nH -> Hub\nI -> Hub\nJ -> Hub";
+        let layouts: [Box<dyn layout::LayoutEngine>; 2] = [
+            Box::new(layout::LayeredLayout),
+            Box::new(layout::ForceDirectedLayout::default()),
+        ];
+        for layout in layouts {
+            let options = options::Options {
+                layout,
+                ..options::Options::default()
+            };
+            let (map, _) = read_input_with_options(input, &options).unwrap();
+
+            assert_eq!(map.arrows.len(), 10);
+            assert!(map.diagnostics.is_empty());
+            let cells = map
+                .arrows
+                .iter()
+                .flat_map(|arrow| [arrow.get_start(), arrow.get_end()])
+                .collect::<BTreeSet<_>>();
+            assert_eq!(cells.len(), 20);
+        }
+    }
+
+    #[test]
+    fn include_arrows_draws_arrows_between_touching_boxes() {
+        let mut map = get_map_with_nodes(&[("A", 0, 0), ("B", 6, 0)]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "B")],
+            None,
+            &HashMap::new(),
+        );
+
+        assert_eq!(map.arrows.len(), 1);
+        assert!(map.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn include_arrows_routes_around_the_outer_border() {
+        let mut map = get_map_with_nodes(&[("A", 0, 0), ("B", 0, 4)]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "A")],
+            None,
+            &HashMap::new(),
+        );
+
+        assert_eq!(map.nodes[&domain::Point { x: 0, y: 2 }].name, "A");
+        assert_eq!(map.nodes[&domain::Point { x: 0, y: 6 }].name, "B");
+        let arrow = map.arrows.iter().next().unwrap();
+        assert_eq!(
+            arrow.points,
+            vec![
+                domain::Point { x: 5, y: 3 },
+                domain::Point { x: 6, y: 3 },
+                domain::Point { x: 6, y: 0 },
+                domain::Point { x: 2, y: 0 },
+                domain::Point { x: 2, y: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn include_arrows_reports_arrows_it_cannot_draw() {
+        let mut map = get_map_with_nodes(&[("A", 0, 0)]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "C")],
+            None,
+            &HashMap::new(),
+        );
+
+        assert!(map.arrows.is_empty());
+        assert_eq!(
+            map.diagnostics,
+            vec!["Line 1: cannot draw the arrow from A to C".to_owned()]
+        );
+    }
+
+    #[test]
+    fn include_arrows_reports_arrows_through_boxes() {
+        let mut map = get_map_with_nodes(&[
+            ("TopTopTopTopT", 0, 1),
+            ("Le", 0, 4),
+            ("A", 6, 4),
+            ("Ri", 11, 4),
+            ("BottomBottomB", 0, 7),
+            ("D", 30, 4),
+        ]);
+        include_arrows(
+            &mut map,
+            &vec![&get_mapping("A", "D")],
+            None,
+            &HashMap::new(),
+        );
+
+        assert_eq!(map.arrows.len(), 1);
+        assert_eq!(
+            map.diagnostics,
+            vec!["Line 1: the arrow from A to D crosses a box".to_owned()]
+        );
+    }
+
+    fn get_arrow(from: &str, to: &str, line: usize, points: &[(usize, usize)]) -> domain::Arrow {
+        let point = domain::Point { x: 0, y: 0 };
+        domain::Arrow {
+            points: points
+                .iter()
+                .map(|(x, y)| domain::Point { x: *x, y: *y })
+                .collect(),
+            ..domain::Arrow::new(from, to, line, point.clone(), point.clone(), point)
+        }
+    }
+
     fn assert_maps(expected: domain::Map, result: domain::Map) {
         assert_eq!(expected.nodes.len(), result.nodes.len());
         for (key, value) in expected.nodes {
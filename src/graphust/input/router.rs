@@ -0,0 +1,410 @@
+use super::{get_heading, Direction, SignedPoint};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const BEND_COST: u32 = 3;
+const ALONGSIDE_COST: u32 = 4;
+/// Free cells around the endpoints searched first. Every retry searches a
+/// window this many times wider.
+const WINDOW_MARGIN: usize = 8;
+const WINDOW_GROWTH: usize = 4;
+/// Keeps a single arrow from searching a large map for long.
+const MAX_EXPANSIONS: usize = 200_000;
+const NO_PARENT: u8 = u8::MAX;
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Top,
+    Direction::Bottom,
+    Direction::Left,
+    Direction::Right,
+];
+
+fn get_index(direction: Direction) -> usize {
+    match direction {
+        Direction::Top => 0,
+        Direction::Bottom => 1,
+        Direction::Left => 2,
+        Direction::Right => 3,
+    }
+}
+
+fn get_opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Top => Direction::Bottom,
+        Direction::Bottom => Direction::Top,
+        Direction::Left => Direction::Right,
+        Direction::Right => Direction::Left,
+    }
+}
+
+fn is_horizontal(direction: Direction) -> bool {
+    matches!(direction, Direction::Left | Direction::Right)
+}
+
+fn get_step(direction: Direction) -> (isize, isize) {
+    match direction {
+        Direction::Left => (-1, 0),
+        Direction::Right => (1, 0),
+        Direction::Top => (0, -1),
+        Direction::Bottom => (0, 1),
+    }
+}
+
+fn get_distance(point: &SignedPoint, other: &SignedPoint) -> u32 {
+    (point.x.abs_diff(other.x) + point.y.abs_diff(other.y)) as u32
+}
+
+/// Cells a search may visit, as grid columns and rows.
+struct Window {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+/// Finds orthogonal arrow paths over the character grid with A*, going
+/// around node boxes. Every bend and every cell already used by an arrow
+/// running the same way adds to the length of a path.
+pub struct Router {
+    min: SignedPoint,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    in_box: Vec<bool>,
+    used: Vec<[bool; 2]>,
+    /// Search state for every cell and heading, valid where its stamp
+    /// matches the current search.
+    costs: Vec<u32>,
+    parents: Vec<u8>,
+    stamps: Vec<u32>,
+    stamp: u32,
+}
+
+impl Router {
+    /// Covers `width` by `height` cells starting at `min`, which may lie left
+    /// of or above the map so routes can pass around its outer border.
+    pub fn new(min: SignedPoint, width: usize, height: usize) -> Self {
+        Self {
+            min,
+            width,
+            height,
+            blocked: vec![false; width * height],
+            in_box: vec![false; width * height],
+            used: vec![[false; 2]; width * height],
+            costs: vec![0; width * height * 4],
+            parents: vec![NO_PARENT; width * height * 4],
+            stamps: vec![0; width * height * 4],
+            stamp: 0,
+        }
+    }
+
+    fn get_index(&self, x: isize, y: isize) -> Option<usize> {
+        let column = usize::try_from(x - self.min.x).ok()?;
+        let row = usize::try_from(y - self.min.y).ok()?;
+        if column < self.width && row < self.height {
+            Some(row * self.width + column)
+        } else {
+            None
+        }
+    }
+
+    pub fn block(&mut self, anchor: &SignedPoint, width: usize, height: usize) {
+        for y in anchor.y..anchor.y + height as isize {
+            for x in anchor.x..anchor.x + width as isize {
+                if let Some(index) = self.get_index(x, y) {
+                    self.blocked[index] = true;
+                    self.in_box[index] = true;
+                }
+            }
+        }
+    }
+
+    /// Keeps routes off a cell outside the boxes.
+    pub fn reserve(&mut self, point: &SignedPoint) {
+        if let Some(index) = self.get_index(point.x, point.y) {
+            self.blocked[index] = true;
+        }
+    }
+
+    fn get_cells(points: &[&SignedPoint]) -> Vec<(isize, isize, Direction)> {
+        let mut cells = Vec::new();
+        for segment in points.windows(2) {
+            let (from, to) = (segment[0], segment[1]);
+            let direction = get_heading(from, to);
+            let (mut x, mut y) = (from.x, from.y);
+            cells.push((x, y, direction));
+            while (x, y) != (to.x, to.y) {
+                let (step_x, step_y) = get_step(direction);
+                (x, y) = (x + step_x, y + step_y);
+                cells.push((x, y, direction));
+            }
+        }
+        cells
+    }
+
+    /// Whether the orthogonal path through the points stays out of boxes and
+    /// off arrows running the same way. Crossing other arrows is fine.
+    pub fn is_clear(&self, points: &[&SignedPoint]) -> bool {
+        Self::get_cells(points).iter().all(|(x, y, direction)| {
+            self.get_index(*x, *y).is_some_and(|index| {
+                !self.blocked[index] && !self.used[index][usize::from(is_horizontal(*direction))]
+            })
+        })
+    }
+
+    /// Whether the orthogonal path through the points runs through a box.
+    pub fn crosses_box(&self, points: &[&SignedPoint]) -> bool {
+        Self::get_cells(points).iter().any(|(x, y, _)| {
+            self.get_index(*x, *y)
+                .is_some_and(|index| self.in_box[index])
+        })
+    }
+
+    pub fn add_path(&mut self, points: &[&SignedPoint]) {
+        for (x, y, direction) in Self::get_cells(points) {
+            if let Some(index) = self.get_index(x, y) {
+                self.used[index][usize::from(is_horizontal(direction))] = true;
+            }
+        }
+    }
+
+    fn get_step_cost(&self, index: usize, heading: Direction) -> u32 {
+        if self.used[index][usize::from(is_horizontal(heading))] {
+            1 + ALONGSIDE_COST
+        } else {
+            1
+        }
+    }
+
+    /// Routes from any of the starts, leaving its box straight out of the
+    /// given side, to any of the ends, entering its box straight through the
+    /// given side. The search starts
+    /// close around the endpoints and only widens when that finds nothing.
+    /// Returns the start, every corner and the end.
+    pub fn route(
+        &mut self,
+        starts: &[(SignedPoint, Direction)],
+        ends: &[(SignedPoint, Direction)],
+    ) -> Option<Vec<SignedPoint>> {
+        let cells = starts
+            .iter()
+            .chain(ends)
+            .filter_map(|(point, _)| {
+                let index = self.get_index(point.x, point.y)?;
+                Some((index % self.width, index / self.width))
+            })
+            .collect::<Vec<_>>();
+        let left = cells.iter().map(|(column, _)| *column).min()?;
+        let right = cells.iter().map(|(column, _)| *column).max()?;
+        let top = cells.iter().map(|(_, row)| *row).min()?;
+        let bottom = cells.iter().map(|(_, row)| *row).max()?;
+
+        let mut margin = WINDOW_MARGIN;
+        loop {
+            let window = Window {
+                left: left.saturating_sub(margin),
+                top: top.saturating_sub(margin),
+                right: (right + margin + 1).min(self.width),
+                bottom: (bottom + margin + 1).min(self.height),
+            };
+            let is_whole_grid = window.left == 0
+                && window.top == 0
+                && window.right == self.width
+                && window.bottom == self.height;
+            match self.search(starts, ends, &window) {
+                Ok(Some(route)) => return Some(route),
+                Ok(None) if !is_whole_grid => margin *= WINDOW_GROWTH,
+                _ => return None,
+            }
+        }
+    }
+
+    /// A* within the window. Fails when it expands too many states.
+    fn search(
+        &mut self,
+        starts: &[(SignedPoint, Direction)],
+        ends: &[(SignedPoint, Direction)],
+        window: &Window,
+    ) -> Result<Option<Vec<SignedPoint>>, ()> {
+        let is_inside = |index: usize| {
+            let (column, row) = (index % self.width, index / self.width);
+            (window.left..window.right).contains(&column)
+                && (window.top..window.bottom).contains(&row)
+        };
+        let get_estimate = |x: isize, y: isize| {
+            ends.iter()
+                .map(|(end, _)| get_distance(&SignedPoint { x, y }, end))
+                .min()
+                .unwrap_or_default()
+        };
+        self.stamp += 1;
+        let mut open = BinaryHeap::new();
+        for (start, side) in starts {
+            let Some(index) = self.get_index(start.x, start.y) else {
+                continue;
+            };
+            if self.blocked[index] || !is_inside(index) {
+                continue;
+            }
+            let state = index * 4 + get_index(*side);
+            self.stamps[state] = self.stamp;
+            self.costs[state] = 0;
+            self.parents[state] = NO_PARENT;
+            open.push(Reverse((get_estimate(start.x, start.y), 0, state)));
+        }
+
+        let mut expansions = 0;
+        while let Some(Reverse((_, cost, state))) = open.pop() {
+            if self.costs[state] < cost {
+                continue;
+            }
+            expansions += 1;
+            if expansions > MAX_EXPANSIONS {
+                return Err(());
+            }
+            let (index, heading) = (state / 4, DIRECTIONS[state % 4]);
+            let x = (index % self.width) as isize + self.min.x;
+            let y = (index / self.width) as isize + self.min.y;
+            let is_end = ends
+                .iter()
+                .any(|(end, side)| end.x == x && end.y == y && heading == get_opposite(*side));
+            if is_end {
+                return Ok(Some(self.get_waypoints(state)));
+            }
+
+            for next_heading in DIRECTIONS {
+                // Arrows take their first step straight out of the box.
+                let is_start = self.parents[state] == NO_PARENT;
+                if next_heading == get_opposite(heading) || (is_start && next_heading != heading) {
+                    continue;
+                }
+                let (step_x, step_y) = get_step(next_heading);
+                let Some(next_index) = self.get_index(x + step_x, y + step_y) else {
+                    continue;
+                };
+                if self.blocked[next_index] || !is_inside(next_index) {
+                    continue;
+                }
+                let next_state = next_index * 4 + get_index(next_heading);
+                let bend_cost = if next_heading != heading {
+                    BEND_COST
+                } else {
+                    0
+                };
+                let next_cost = cost + bend_cost + self.get_step_cost(next_index, next_heading);
+                if self.stamps[next_state] == self.stamp && self.costs[next_state] <= next_cost {
+                    continue;
+                }
+                self.stamps[next_state] = self.stamp;
+                self.costs[next_state] = next_cost;
+                self.parents[next_state] = get_index(heading) as u8;
+                open.push(Reverse((
+                    next_cost + get_estimate(x + step_x, y + step_y),
+                    next_cost,
+                    next_state,
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    fn get_point(&self, state: usize) -> SignedPoint {
+        let index = state / 4;
+        SignedPoint {
+            x: (index % self.width) as isize + self.min.x,
+            y: (index / self.width) as isize + self.min.y,
+        }
+    }
+
+    /// Walks back from the end. Every state was entered moving along its
+    /// heading, so its parent lies one step against it.
+    fn get_waypoints(&self, end: usize) -> Vec<SignedPoint> {
+        let mut states = vec![end];
+        loop {
+            let state = *states.last().unwrap();
+            let parent_heading = self.parents[state];
+            if parent_heading == NO_PARENT {
+                break;
+            }
+            let (step_x, step_y) = get_step(DIRECTIONS[state % 4]);
+            let point = self.get_point(state);
+            let parent_index = self.get_index(point.x - step_x, point.y - step_y).unwrap();
+            states.push(parent_index * 4 + usize::from(parent_heading));
+        }
+        states.reverse();
+
+        let mut waypoints = vec![self.get_point(states[0])];
+        for pair in states.windows(2).skip(1) {
+            if pair[0] % 4 != pair[1] % 4 {
+                waypoints.push(self.get_point(pair[0]));
+            }
+        }
+        if states.len() > 1 {
+            waypoints.push(self.get_point(end));
+        }
+        waypoints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_goes_around_boxes() {
+        let mut router = Router::new(SignedPoint { x: 0, y: 0 }, 20, 10);
+        router.block(&SignedPoint { x: 5, y: 0 }, 5, 5);
+        let path = router
+            .route(
+                &[(SignedPoint { x: 0, y: 2 }, Direction::Right)],
+                &[(SignedPoint { x: 15, y: 2 }, Direction::Left)],
+            )
+            .unwrap();
+
+        assert_eq!(path.first(), Some(&SignedPoint { x: 0, y: 2 }));
+        assert_eq!(path.last(), Some(&SignedPoint { x: 15, y: 2 }));
+        let points = path.iter().collect::<Vec<_>>();
+        assert!(router.is_clear(&points));
+        assert_eq!(path[1].y, 2);
+        assert_eq!(path.len(), 6);
+    }
+
+    #[test]
+    fn route_widens_the_search_around_long_walls() {
+        let mut router = Router::new(SignedPoint { x: 0, y: 0 }, 20, 60);
+        router.block(&SignedPoint { x: 5, y: 0 }, 2, 50);
+        let path = router
+            .route(
+                &[(SignedPoint { x: 0, y: 2 }, Direction::Right)],
+                &[(SignedPoint { x: 15, y: 2 }, Direction::Left)],
+            )
+            .unwrap();
+
+        assert!(path.iter().any(|point| point.y >= 50));
+        assert!(router.is_clear(&path.iter().collect::<Vec<_>>()));
+    }
+
+    #[test]
+    fn route_avoids_running_along_other_arrows() {
+        let mut router = Router::new(SignedPoint { x: 0, y: 0 }, 20, 10);
+        router.add_path(&[&SignedPoint { x: 0, y: 2 }, &SignedPoint { x: 19, y: 2 }]);
+        let path = router
+            .route(
+                &[(SignedPoint { x: 0, y: 2 }, Direction::Right)],
+                &[(SignedPoint { x: 19, y: 2 }, Direction::Left)],
+            )
+            .unwrap();
+
+        assert!(path.len() > 2);
+    }
+
+    #[test]
+    fn is_clear_rejects_paths_on_top_of_other_arrows() {
+        let mut router = Router::new(SignedPoint { x: 0, y: 0 }, 20, 10);
+        router.add_path(&[&SignedPoint { x: 0, y: 2 }, &SignedPoint { x: 19, y: 2 }]);
+
+        let along = [&SignedPoint { x: 5, y: 2 }, &SignedPoint { x: 10, y: 2 }];
+        let across = [&SignedPoint { x: 7, y: 0 }, &SignedPoint { x: 7, y: 5 }];
+        assert!(!router.is_clear(&along));
+        assert!(router.is_clear(&across));
+    }
+}
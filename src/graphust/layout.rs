@@ -0,0 +1,495 @@
+mod cache;
+mod components;
+mod crossings;
+mod force_directed_graph;
+mod layered_graph;
+
+use crate::graphust::stats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutDirection {
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+}
+impl LayoutDirection {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "LR" => Ok(LayoutDirection::LeftToRight),
+            "RL" => Ok(LayoutDirection::RightToLeft),
+            "TB" => Ok(LayoutDirection::TopToBottom),
+            "BT" => Ok(LayoutDirection::BottomToTop),
+            _ => Err(format!("Unknown direction: {}", name)),
+        }
+    }
+
+    /// The direction after swapping the axes of a layout.
+    pub fn get_transposed(&self) -> Self {
+        match self {
+            LayoutDirection::LeftToRight => LayoutDirection::TopToBottom,
+            LayoutDirection::RightToLeft => LayoutDirection::BottomToTop,
+            LayoutDirection::TopToBottom => LayoutDirection::LeftToRight,
+            LayoutDirection::BottomToTop => LayoutDirection::RightToLeft,
+        }
+    }
+
+    fn get_vector(&self) -> (isize, isize) {
+        match self {
+            LayoutDirection::LeftToRight => (1, 0),
+            LayoutDirection::RightToLeft => (-1, 0),
+            LayoutDirection::TopToBottom => (0, 1),
+            LayoutDirection::BottomToTop => (0, -1),
+        }
+    }
+}
+
+/// Order in which disconnected parts of a graph are packed, starting at the
+/// top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentOrder {
+    Size,
+    FirstAppearance,
+}
+impl ComponentOrder {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "size" => Ok(ComponentOrder::Size),
+            "input" => Ok(ComponentOrder::FirstAppearance),
+            _ => Err(format!("Unknown component order: {}", name)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PointApproximation {
+    pub y: usize,
+    pub x: usize,
+}
+
+#[derive(Clone)]
+pub struct NodeApproximation {
+    pub name: String,
+    pub position: PointApproximation,
+}
+
+/// Placement requests from the input, naming nodes by their index in
+/// `LayoutInput::nodes`. Pins are given in grid steps of `PIN_SPACING`
+/// layout units, while kept nodes stay at a position of an earlier layout.
+/// Ranks follow the flow direction, aligned nodes share the position across
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutConstraint {
+    Pin { node: usize, x: usize, y: usize },
+    Keep { node: usize, x: usize, y: usize },
+    LeftOf(usize, usize),
+    Above(usize, usize),
+    SameRank(Vec<usize>),
+    Aligned(Vec<usize>),
+}
+
+pub use cache::LayoutCache;
+
+pub const PIN_SPACING: usize = 5;
+
+/// Nodes in order of first appearance and directed edges given as indices
+/// into `nodes`. Duplicate edges and self references are kept as written.
+pub struct LayoutInput<'a> {
+    pub nodes: &'a [String],
+    pub edges: &'a [(usize, usize)],
+    pub direction: Option<LayoutDirection>,
+    pub constraints: &'a [LayoutConstraint],
+    /// Draws a layout and measures the drawing, so engines can compare
+    /// candidate layouts the way they will be shown.
+    pub measure: &'a dyn Fn(&[NodeApproximation]) -> stats::Stats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// Constraints in terms of axes, as the engines apply them. Pins are in
+/// layout units.
+#[derive(Debug, Clone, PartialEq)]
+enum Hint {
+    Pin(usize, usize, usize),
+    Order(Axis, usize, usize),
+    Same(Axis, Vec<usize>),
+}
+impl Hint {
+    fn get_nodes(&self) -> Vec<usize> {
+        match self {
+            Hint::Pin(node, _, _) => vec![*node],
+            Hint::Order(_, before, after) => vec![*before, *after],
+            Hint::Same(_, nodes) => nodes.clone(),
+        }
+    }
+
+    fn map_nodes(&self, map: impl Fn(usize) -> usize) -> Hint {
+        match self {
+            Hint::Pin(node, x, y) => Hint::Pin(map(*node), *x, *y),
+            Hint::Order(axis, before, after) => Hint::Order(*axis, map(*before), map(*after)),
+            Hint::Same(axis, nodes) => {
+                Hint::Same(*axis, nodes.iter().map(|node| map(*node)).collect())
+            }
+        }
+    }
+}
+
+fn get_hints(input: &LayoutInput) -> Vec<Hint> {
+    let (main_axis, cross_axis) = match input.direction {
+        Some(LayoutDirection::LeftToRight | LayoutDirection::RightToLeft) => (Axis::X, Axis::Y),
+        _ => (Axis::Y, Axis::X),
+    };
+    input
+        .constraints
+        .iter()
+        .map(|constraint| match constraint {
+            LayoutConstraint::Pin { node, x, y } => {
+                Hint::Pin(*node, x * PIN_SPACING, y * PIN_SPACING)
+            }
+            LayoutConstraint::Keep { node, x, y } => Hint::Pin(*node, *x, *y),
+            LayoutConstraint::LeftOf(left, right) => Hint::Order(Axis::X, *left, *right),
+            LayoutConstraint::Above(top, bottom) => Hint::Order(Axis::Y, *top, *bottom),
+            LayoutConstraint::SameRank(nodes) => Hint::Same(main_axis, nodes.clone()),
+            LayoutConstraint::Aligned(nodes) => Hint::Same(cross_axis, nodes.clone()),
+        })
+        .collect()
+}
+
+pub trait LayoutEngine {
+    fn layout(&self, input: &LayoutInput) -> Vec<NodeApproximation>;
+
+    /// The direction the engine lays out in when given `direction`. Arrows
+    /// are drawn for this direction.
+    fn get_direction(&self, direction: Option<LayoutDirection>) -> Option<LayoutDirection> {
+        direction
+    }
+}
+
+/// Settings of the force simulation, which lays out every disconnected part
+/// of the graph on its own and packs the parts together.
+pub struct ForceDirectedLayout {
+    pub iterations: usize,
+    pub attraction_strength: f32,
+    pub repulsion_strength: f32,
+    /// Largest movement of a node in the first step.
+    pub initial_temperature: f32,
+    /// Multiplies the temperature after each step.
+    pub cooling_factor: f32,
+    /// Stops the simulation once the average movement per node is smaller.
+    pub tolerance: f32,
+    /// Approximates the repulsion of distant groups of nodes with a quadtree
+    /// on large graphs; 0 keeps it exact.
+    pub theta: f32,
+    /// Prints edge crossings and the score of every start to stderr.
+    pub debug: bool,
+    /// Space between packed parts of the graph in layout units, each drawn
+    /// as up to 4 columns across or 1 row down.
+    pub component_gap: usize,
+    pub component_order: ComponentOrder,
+    /// Number of runs, from the positions given by `seed`, `seed + 1` and so
+    /// on. The best scoring layout is kept.
+    pub starts: usize,
+    /// Seed 0 starts from the plain grid, others shuffle it.
+    pub seed: u64,
+}
+impl Default for ForceDirectedLayout {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            attraction_strength: 1.0,
+            repulsion_strength: 1.0,
+            initial_temperature: 10.0,
+            cooling_factor: 0.98,
+            tolerance: 0.001,
+            theta: 0.8,
+            debug: false,
+            component_gap: 3,
+            component_order: ComponentOrder::Size,
+            starts: 1,
+            seed: 0,
+        }
+    }
+}
+struct LaidOutComponent {
+    approximation: Vec<NodeApproximation>,
+    is_pinned: bool,
+    crossings_before: usize,
+    crossings_after: usize,
+}
+
+impl ForceDirectedLayout {
+    /// Runs the simulation on one part of the graph, then swaps nodes while
+    /// that removes edge crossings. Pinned nodes stay where they are and the
+    /// other hints are enforced after every step; parts with hints keep their
+    /// orientation.
+    fn layout_component(
+        &self,
+        input: &LayoutInput,
+        hints: &[Hint],
+        component: &[usize],
+        seed: u64,
+    ) -> LaidOutComponent {
+        let mut local_indices = vec![None; input.nodes.len()];
+        for (local_index, index) in component.iter().enumerate() {
+            local_indices[*index] = Some(local_index);
+        }
+        let nodes = component
+            .iter()
+            .map(|index| input.nodes[*index].clone())
+            .collect::<Vec<_>>();
+        let edges = input
+            .edges
+            .iter()
+            .filter_map(|(from_index, to_index)| {
+                Some((local_indices[*from_index]?, local_indices[*to_index]?))
+            })
+            .collect::<Vec<_>>();
+        // Hinted nodes always share a component.
+        let hints = hints
+            .iter()
+            .filter(|hint| {
+                hint.get_nodes()
+                    .first()
+                    .is_some_and(|node| local_indices[*node].is_some())
+            })
+            .map(|hint| hint.map_nodes(|node| local_indices[node].unwrap()))
+            .collect::<Vec<_>>();
+        let fixed = hints.iter().flat_map(Hint::get_nodes).collect::<Vec<_>>();
+
+        let mut graph = force_directed_graph::Graph::new();
+        for node in &nodes {
+            graph.add_node(node);
+        }
+        for (from_index, to_index) in &edges {
+            graph.add_edge(&nodes[*from_index], &nodes[*to_index]);
+        }
+        if seed != 0 {
+            graph.shuffle(seed);
+        }
+        graph.set_hints(hints.clone());
+        let mut approximation = graph.force_directed(self);
+        let crossings_before = crossings::count_crossings(&approximation, &edges, &nodes);
+        crossings::minimise_crossings(&mut approximation, &edges, &nodes, &fixed);
+        let crossings_after = crossings::count_crossings(&approximation, &edges, &nodes);
+        if let (Some(direction), true) = (input.direction, hints.is_empty()) {
+            let component_input = LayoutInput {
+                nodes: &nodes,
+                edges: &edges,
+                direction: input.direction,
+                constraints: &[],
+                measure: input.measure,
+            };
+            orient(&mut approximation, &component_input, direction);
+        }
+        LaidOutComponent {
+            approximation,
+            is_pinned: hints.iter().any(|hint| matches!(hint, Hint::Pin(..))),
+            crossings_before,
+            crossings_after,
+        }
+    }
+
+    /// Lays out every component from the starting positions given by `seed`
+    /// and packs them. Also returns the crossings before and after nodes
+    /// swapped places.
+    fn layout_with_seed(
+        &self,
+        input: &LayoutInput,
+        seed: u64,
+    ) -> (Vec<NodeApproximation>, usize, usize) {
+        let hints = get_hints(input);
+        // Hints and pins tie their nodes together, and the pinned component
+        // goes first so it keeps the origin.
+        let mut links = input.edges.to_vec();
+        let pinned = hints.iter().filter(|hint| matches!(hint, Hint::Pin(..)));
+        for hint in hints.iter().filter(|hint| !matches!(hint, Hint::Pin(..))) {
+            let nodes = hint.get_nodes();
+            links.extend(nodes.windows(2).map(|pair| (pair[0], pair[1])));
+        }
+        let pinned_nodes = pinned.flat_map(Hint::get_nodes).collect::<Vec<_>>();
+        links.extend(pinned_nodes.windows(2).map(|pair| (pair[0], pair[1])));
+
+        let mut components = components::get_components(input.nodes.len(), &links)
+            .iter()
+            .map(|component| self.layout_component(input, &hints, component, seed))
+            .collect::<Vec<_>>();
+        match self.component_order {
+            ComponentOrder::Size => components.sort_by_key(|component| {
+                (
+                    !component.is_pinned,
+                    std::cmp::Reverse(component.approximation.len()),
+                )
+            }),
+            ComponentOrder::FirstAppearance => {
+                components.sort_by_key(|component| !component.is_pinned)
+            }
+        }
+        let crossings_before = components
+            .iter()
+            .map(|component| component.crossings_before)
+            .sum();
+        let crossings_after = components
+            .iter()
+            .map(|component| component.crossings_after)
+            .sum();
+        let approximation = components::pack(
+            components
+                .into_iter()
+                .map(|component| component.approximation)
+                .collect(),
+            self.component_gap,
+        );
+        (approximation, crossings_before, crossings_after)
+    }
+}
+impl LayoutEngine for ForceDirectedLayout {
+    fn layout(&self, input: &LayoutInput) -> Vec<NodeApproximation> {
+        // Drawing a layout takes far longer than laying it out, so a single
+        // start is only measured for the debug report.
+        let is_measured = self.starts > 1 || self.debug;
+        let mut best: Option<(usize, u64, Vec<NodeApproximation>, usize, usize)> = None;
+        for seed in (0..self.starts.max(1) as u64).map(|start| self.seed.wrapping_add(start)) {
+            let (approximation, crossings_before, crossings_after) =
+                self.layout_with_seed(input, seed);
+            let score = if is_measured {
+                let stats = (input.measure)(&approximation);
+                if self.debug {
+                    eprintln!(
+                        "Debug: seed {} scores {} ({} crossings, {} bends, {} overlaps, {} edges through boxes, area {})",
+                        seed,
+                        stats.get_score(),
+                        stats.crossings,
+                        stats.bends,
+                        stats.overlaps,
+                        stats.edges_through_boxes,
+                        stats.area
+                    );
+                }
+                stats.get_score()
+            } else {
+                0
+            };
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, ..)| score < *best_score)
+            {
+                best = Some((
+                    score,
+                    seed,
+                    approximation,
+                    crossings_before,
+                    crossings_after,
+                ));
+            }
+        }
+        let (_, seed, approximation, crossings_before, crossings_after) = best.unwrap();
+        if self.debug {
+            eprintln!("Debug: kept the layout of seed {}", seed);
+            eprintln!(
+                "Debug: {} edge crossings before reordering nodes, {} after",
+                crossings_before, crossings_after
+            );
+        }
+        approximation
+    }
+}
+
+pub struct LayeredLayout;
+impl LayoutEngine for LayeredLayout {
+    fn layout(&self, input: &LayoutInput) -> Vec<NodeApproximation> {
+        let mut graph = layered_graph::Graph::new();
+        for node in input.nodes {
+            graph.add_node(node);
+        }
+        for (from_index, to_index) in input.edges {
+            graph.add_edge(&input.nodes[*from_index], &input.nodes[*to_index]);
+        }
+        graph.layered(
+            self.get_direction(input.direction).unwrap(),
+            &get_hints(input),
+        )
+    }
+
+    fn get_direction(&self, direction: Option<LayoutDirection>) -> Option<LayoutDirection> {
+        Some(direction.unwrap_or(LayoutDirection::TopToBottom))
+    }
+}
+
+type Transform = fn(isize, isize) -> (isize, isize);
+
+/// Mirrors or rotates a finished layout, so that most edges point along
+/// `direction`. Layouts which already ignore orientation, like the force
+/// simulation, can use this instead of steering every node.
+pub fn orient(
+    approximation: &mut [NodeApproximation],
+    input: &LayoutInput,
+    direction: LayoutDirection,
+) {
+    let transforms: [Transform; 8] = [
+        |x, y| (x, y),
+        |x, y| (-x, y),
+        |x, y| (x, -y),
+        |x, y| (-x, -y),
+        |x, y| (y, x),
+        |x, y| (-y, x),
+        |x, y| (y, -x),
+        |x, y| (-y, -x),
+    ];
+    let (direction_x, direction_y) = direction.get_vector();
+    let get_position = |name: &str, approximation: &[NodeApproximation]| {
+        approximation
+            .iter()
+            .find(|node| node.name == name)
+            .map(|node| (node.position.x as isize, node.position.y as isize))
+    };
+    let edge_vectors = input
+        .edges
+        .iter()
+        .filter_map(|(from_index, to_index)| {
+            let from = get_position(&input.nodes[*from_index], approximation)?;
+            let to = get_position(&input.nodes[*to_index], approximation)?;
+            Some((to.0 - from.0, to.1 - from.1))
+        })
+        .collect::<Vec<_>>();
+    let (_, best_transform) = transforms
+        .iter()
+        .enumerate()
+        .max_by_key(|(index, transform)| {
+            let score = edge_vectors
+                .iter()
+                .map(|(x, y)| {
+                    let (x, y) = transform(*x, *y);
+                    x * direction_x + y * direction_y
+                })
+                .sum::<isize>();
+            // Earlier transforms win ties, so an already oriented layout
+            // stays untouched.
+            (score, std::cmp::Reverse(*index))
+        })
+        .unwrap();
+
+    let transformed = approximation
+        .iter()
+        .map(|node| best_transform(node.position.x as isize, node.position.y as isize))
+        .collect::<Vec<_>>();
+    let min_x = transformed.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let min_y = transformed.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    for (node, (x, y)) in approximation.iter_mut().zip(transformed) {
+        node.position = PointApproximation {
+            x: (x - min_x) as usize,
+            y: (y - min_y) as usize,
+        };
+    }
+}
+
+pub fn get_engine(name: &str) -> Result<Box<dyn LayoutEngine>, String> {
+    match name {
+        "force-directed" => Ok(Box::new(ForceDirectedLayout::default())),
+        "layered" => Ok(Box::new(LayeredLayout)),
+        _ => Err(format!("Unknown layout: {}", name)),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::graphust::layout::NodeApproximation;
+use std::collections::BTreeMap;
+
+/// Node positions of an earlier layout, kept in a sidecar file so the layout
+/// of an edited input can start from them. Every line holds `x y name`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutCache {
+    positions: BTreeMap<String, (usize, usize)>,
+}
+
+impl LayoutCache {
+    pub fn from_approximation(approximation: &[NodeApproximation]) -> Self {
+        Self {
+            positions: approximation
+                .iter()
+                .map(|node| (node.name.clone(), (node.position.x, node.position.y)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<(usize, usize)> {
+        self.positions.get(name).copied()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut positions = BTreeMap::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let mut parts = line.splitn(3, ' ');
+            let (Some(x), Some(y), Some(name)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(format!(
+                    "Cannot understand this layout cache line: {}",
+                    line
+                ));
+            };
+            let (Ok(x), Ok(y)) = (x.parse(), y.parse()) else {
+                return Err(format!(
+                    "Cannot understand this layout cache line: {}",
+                    line
+                ));
+            };
+            positions.insert(name.to_owned(), (x, y));
+        }
+        Ok(Self { positions })
+    }
+
+    pub fn to_text(&self) -> String {
+        self.positions
+            .iter()
+            .map(|(name, (x, y))| format!("{} {} {}\n", x, y, name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphust::layout::PointApproximation;
+
+    #[test]
+    fn layout_cache_round_trip() {
+        let cache = LayoutCache::from_approximation(&[
+            NodeApproximation {
+                name: "This is our test".to_owned(),
+                position: PointApproximation { x: 3, y: 7 },
+            },
+            NodeApproximation {
+                name: "B".to_owned(),
+                position: PointApproximation { x: 0, y: 0 },
+            },
+        ]);
+        let text = cache.to_text();
+        assert_eq!(text, "0 0 B\n3 7 This is our test\n");
+        assert_eq!(LayoutCache::parse(&text), Ok(cache));
+    }
+
+    #[test]
+    fn layout_cache_rejects_bad_lines() {
+        assert_eq!(
+            LayoutCache::parse("1 B"),
+            Err("Cannot understand this layout cache line: 1 B".to_owned())
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::graphust::layout::{NodeApproximation, PointApproximation};
+
+/// Groups node indices into connected components. Components and the nodes
+/// inside them are listed in order of first appearance.
+pub fn get_components(node_count: usize, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut adjacency = vec![Vec::new(); node_count];
+    for (from_index, to_index) in edges {
+        adjacency[*from_index].push(*to_index);
+        adjacency[*to_index].push(*from_index);
+    }
+
+    let mut component_indices: Vec<Option<usize>> = vec![None; node_count];
+    let mut components: Vec<Vec<usize>> = Vec::new();
+    for start in 0..node_count {
+        if component_indices[start].is_some() {
+            continue;
+        }
+        let mut stack = vec![start];
+        component_indices[start] = Some(components.len());
+        while let Some(index) = stack.pop() {
+            for neighbour in &adjacency[index] {
+                if component_indices[*neighbour].is_none() {
+                    component_indices[*neighbour] = Some(components.len());
+                    stack.push(*neighbour);
+                }
+            }
+        }
+        components.push(Vec::new());
+    }
+    for (index, component_index) in component_indices.into_iter().enumerate() {
+        components[component_index.unwrap()].push(index);
+    }
+    components
+}
+
+fn get_size(component: &[NodeApproximation]) -> (usize, usize) {
+    let width = component.iter().map(|node| node.position.x + 1).max();
+    let height = component.iter().map(|node| node.position.y + 1).max();
+    (width.unwrap_or(0), height.unwrap_or(0))
+}
+
+/// Places laid out components left to right in rows about as wide as the
+/// whole packing is tall, keeping `gap` layout units between them.
+pub fn pack(components: Vec<Vec<NodeApproximation>>, gap: usize) -> Vec<NodeApproximation> {
+    let sizes = components
+        .iter()
+        .map(|component| get_size(component))
+        .collect::<Vec<_>>();
+    let area = sizes
+        .iter()
+        .map(|(width, height)| (width + gap) * (height + gap))
+        .sum::<usize>();
+    let row_width = sizes
+        .iter()
+        .map(|(width, _)| *width)
+        .max()
+        .unwrap_or(0)
+        .max((area as f32).sqrt().ceil() as usize);
+
+    let mut packed = Vec::new();
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for (component, (width, height)) in components.into_iter().zip(sizes) {
+        if x > 0 && x + width > row_width {
+            x = 0;
+            y += row_height + gap;
+            row_height = 0;
+        }
+        packed.extend(component.into_iter().map(|node| NodeApproximation {
+            name: node.name,
+            position: PointApproximation {
+                x: node.position.x + x,
+                y: node.position.y + y,
+            },
+        }));
+        x += width + gap;
+        row_height = row_height.max(height);
+    }
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_component(name: &str, width: usize, height: usize) -> Vec<NodeApproximation> {
+        vec![
+            NodeApproximation {
+                name: format!("{}1", name),
+                position: PointApproximation { x: 0, y: 0 },
+            },
+            NodeApproximation {
+                name: format!("{}2", name),
+                position: PointApproximation {
+                    x: width - 1,
+                    y: height - 1,
+                },
+            },
+        ]
+    }
+
+    #[test]
+    fn get_components_in_order_of_appearance() {
+        let components = get_components(6, &[(0, 3), (1, 4), (3, 5)]);
+        assert_eq!(components, vec![vec![0, 3, 5], vec![1, 4], vec![2]]);
+    }
+
+    #[test]
+    fn pack_keeps_gap_between_components() {
+        let components = (0..4)
+            .map(|index| get_component(&index.to_string(), 10, 10))
+            .collect::<Vec<_>>();
+        let packed = pack(components, 2);
+
+        let (width, height) = get_size(&packed);
+        assert_eq!((width, height), (22, 22));
+        let corners = packed
+            .iter()
+            .filter(|node| node.name.ends_with('1'))
+            .map(|node| (node.position.x, node.position.y))
+            .collect::<Vec<_>>();
+        assert_eq!(corners, vec![(0, 0), (12, 0), (0, 12), (12, 12)]);
+    }
+}
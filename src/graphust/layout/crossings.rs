@@ -0,0 +1,189 @@
+use crate::graphust::layout::{NodeApproximation, PointApproximation};
+use std::collections::HashMap;
+
+/// Every pass tries all pairs of nodes, so larger layouts keep their positions.
+const MAX_NODES: usize = 64;
+const MAX_PASSES: usize = 10;
+
+type Position = (isize, isize);
+
+fn get_orientation(from: Position, to: Position, point: Position) -> isize {
+    ((to.0 - from.0) * (point.1 - from.1) - (to.1 - from.1) * (point.0 - from.0)).signum()
+}
+
+/// Whether two straight edges cross. Edges sharing a node never do.
+fn edges_cross(positions: &[Position], edge: (usize, usize), other: (usize, usize)) -> bool {
+    if edge.0 == other.0 || edge.0 == other.1 || edge.1 == other.0 || edge.1 == other.1 {
+        return false;
+    }
+    let (from, to) = (positions[edge.0], positions[edge.1]);
+    let (other_from, other_to) = (positions[other.0], positions[other.1]);
+    get_orientation(from, to, other_from) * get_orientation(from, to, other_to) < 0
+        && get_orientation(other_from, other_to, from) * get_orientation(other_from, other_to, to)
+            < 0
+}
+
+fn is_bent(positions: &[Position], edge: (usize, usize)) -> bool {
+    let (from, to) = (positions[edge.0], positions[edge.1]);
+    from.0 != to.0 && from.1 != to.1
+}
+
+/// Crossings and bends of the given edges, counting every pair of crossing
+/// edges once.
+fn get_score(positions: &[Position], edges: &[(usize, usize)], local: &[usize]) -> (usize, usize) {
+    let mut crossings = 0;
+    for edge_index in local {
+        for (other_index, other) in edges.iter().enumerate() {
+            let counted_elsewhere =
+                other_index <= *edge_index && local.binary_search(&other_index).is_ok();
+            if !counted_elsewhere && edges_cross(positions, edges[*edge_index], *other) {
+                crossings += 1;
+            }
+        }
+    }
+    let bends = local
+        .iter()
+        .filter(|edge_index| is_bent(positions, edges[**edge_index]))
+        .count();
+    (crossings, bends)
+}
+
+fn get_positions_and_edges(
+    approximation: &[NodeApproximation],
+    edges: &[(usize, usize)],
+    nodes: &[String],
+) -> (Vec<Position>, Vec<(usize, usize)>) {
+    let indices = approximation
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.name.as_str(), index))
+        .collect::<HashMap<_, _>>();
+    let positions = approximation
+        .iter()
+        .map(|node| (node.position.x as isize, node.position.y as isize))
+        .collect();
+    let mut unique_edges = edges
+        .iter()
+        .filter_map(|(from_index, to_index)| {
+            let from = *indices.get(nodes[*from_index].as_str())?;
+            let to = *indices.get(nodes[*to_index].as_str())?;
+            (from != to).then_some((from.min(to), from.max(to)))
+        })
+        .collect::<Vec<_>>();
+    unique_edges.sort();
+    unique_edges.dedup();
+    (positions, unique_edges)
+}
+
+pub fn count_crossings(
+    approximation: &[NodeApproximation],
+    edges: &[(usize, usize)],
+    nodes: &[String],
+) -> usize {
+    let (positions, edges) = get_positions_and_edges(approximation, edges, nodes);
+    get_score(&positions, &edges, &(0..edges.len()).collect::<Vec<_>>()).0
+}
+
+/// Swaps the positions of pairs of nodes while that lowers the number of
+/// crossing edges, or the number of edges that are not straight lines at the
+/// same crossings. Pairs are tried in node order, so the result is
+/// deterministic. The `fixed` nodes keep their positions.
+pub fn minimise_crossings(
+    approximation: &mut [NodeApproximation],
+    edges: &[(usize, usize)],
+    nodes: &[String],
+    fixed: &[usize],
+) {
+    if approximation.len() > MAX_NODES {
+        return;
+    }
+    let (mut positions, edges) = get_positions_and_edges(approximation, edges, nodes);
+    let mut is_fixed = vec![false; positions.len()];
+    for node in fixed {
+        if let Some(index) = approximation
+            .iter()
+            .position(|approximated| approximated.name == nodes[*node])
+        {
+            is_fixed[index] = true;
+        }
+    }
+    let mut incident = vec![Vec::new(); positions.len()];
+    for (edge_index, (from, to)) in edges.iter().enumerate() {
+        incident[*from].push(edge_index);
+        incident[*to].push(edge_index);
+    }
+
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for node in (0..positions.len()).filter(|node| !is_fixed[*node]) {
+            for other in (node + 1..positions.len()).filter(|other| !is_fixed[*other]) {
+                let mut local = [incident[node].as_slice(), incident[other].as_slice()].concat();
+                local.sort();
+                local.dedup();
+                let score = get_score(&positions, &edges, &local);
+                positions.swap(node, other);
+                if get_score(&positions, &edges, &local) < score {
+                    improved = true;
+                } else {
+                    positions.swap(node, other);
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    for (node, (x, y)) in approximation.iter_mut().zip(positions) {
+        node.position = PointApproximation {
+            x: x as usize,
+            y: y as usize,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_approximation(positions: &[(&str, usize, usize)]) -> Vec<NodeApproximation> {
+        positions
+            .iter()
+            .map(|(name, x, y)| NodeApproximation {
+                name: name.to_string(),
+                position: PointApproximation { x: *x, y: *y },
+            })
+            .collect()
+    }
+
+    fn get_names(approximation: &[NodeApproximation]) -> Vec<String> {
+        approximation.iter().map(|node| node.name.clone()).collect()
+    }
+
+    #[test]
+    fn count_crossings_ignores_edges_sharing_a_node() {
+        let approximation = get_approximation(&[("A", 0, 0), ("B", 4, 4), ("C", 4, 0)]);
+        let nodes = get_names(&approximation);
+        assert_eq!(
+            count_crossings(&approximation, &[(0, 1), (0, 2), (1, 2)], &nodes),
+            0
+        );
+    }
+
+    #[test]
+    fn minimise_crossings_untangles_crossing_edges() {
+        let mut approximation =
+            get_approximation(&[("A", 0, 0), ("B", 4, 4), ("C", 4, 0), ("D", 0, 4)]);
+        let nodes = get_names(&approximation);
+        let edges = [(0, 1), (2, 3)];
+        assert_eq!(count_crossings(&approximation, &edges, &nodes), 1);
+
+        minimise_crossings(&mut approximation, &edges, &nodes, &[]);
+
+        assert_eq!(count_crossings(&approximation, &edges, &nodes), 0);
+        for (from, to) in edges {
+            let (from, to) = (&approximation[from].position, &approximation[to].position);
+            assert!(from.x == to.x || from.y == to.y);
+        }
+    }
+}
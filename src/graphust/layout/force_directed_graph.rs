@@ -0,0 +1,744 @@
+use crate::graphust::layout::{
+    Axis, ForceDirectedLayout, Hint, NodeApproximation, PointApproximation, PIN_SPACING,
+};
+use std::collections::{HashMap, HashSet};
+
+mod quad_tree;
+
+/// Below this many nodes the exact pairwise repulsion is cheap enough.
+const BARNES_HUT_THRESHOLD: usize = 64;
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    y: f32,
+    x: f32,
+}
+
+struct Node {
+    name: String,
+    position: Point,
+    is_pinned: bool,
+}
+
+struct Edge {
+    from_index: usize,
+    to_index: usize,
+}
+
+struct InnerGraphPlacement {
+    next_x: f32,
+    next_y: f32,
+    offset: f32,
+    max_x_count: usize,
+}
+
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    node_indices: HashMap<String, usize>,
+    edge_set: HashSet<(usize, usize)>,
+    hints: Vec<Hint>,
+    placement: InnerGraphPlacement,
+}
+
+impl InnerGraphPlacement {
+    fn new() -> InnerGraphPlacement {
+        InnerGraphPlacement {
+            next_x: 0.0,
+            next_y: 0.0,
+            offset: 5.0,
+            max_x_count: 3,
+        }
+    }
+    fn get_next_position(&mut self) -> Point {
+        let position = Point {
+            x: self.next_x,
+            y: self.next_y,
+        };
+        self.next_x += self.offset;
+        if self.next_x > ((self.max_x_count - 1) as f32 * self.offset) {
+            self.next_x = 0.0;
+            self.next_y += self.offset;
+        }
+        position
+    }
+}
+
+/// SplitMix64, enough to shuffle starting positions reproducibly.
+struct Random(u64);
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    fn next_below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+fn get_coordinate(point: &mut Point, axis: Axis) -> &mut f32 {
+    match axis {
+        Axis::X => &mut point.x,
+        Axis::Y => &mut point.y,
+    }
+}
+
+fn calculate_distance(point1: &Point, point2: &Point) -> f32 {
+    ((point2.x - point1.x).powi(2) + (point2.y - point1.y).powi(2)).sqrt()
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            node_indices: HashMap::new(),
+            edge_set: HashSet::new(),
+            hints: Vec::new(),
+            placement: InnerGraphPlacement::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, node_label: &str) {
+        if self.node_indices.contains_key(node_label) {
+            return;
+        }
+
+        self.node_indices
+            .insert(node_label.to_string(), self.nodes.len());
+        self.nodes.push(Node {
+            name: node_label.to_string(),
+            position: self.placement.get_next_position(),
+            is_pinned: false,
+        });
+    }
+
+    pub fn add_edge(&mut self, node_from_label: &str, node_to_label: &str) {
+        if node_from_label == node_to_label {
+            return;
+        }
+
+        let (Some(from_index), Some(to_index)) = (
+            self.node_indices.get(node_from_label),
+            self.node_indices.get(node_to_label),
+        ) else {
+            panic!("Could not find both nodes");
+        };
+        let key = (
+            usize::min(*from_index, *to_index),
+            usize::max(*from_index, *to_index),
+        );
+        if !self.edge_set.insert(key) {
+            return;
+        }
+
+        self.edges.push(Edge {
+            from_index: key.0,
+            to_index: key.1,
+        });
+    }
+
+    /// Deals the grid positions out to the nodes in a random order picked by
+    /// `seed`, and moves each node a little off the grid.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut random = Random(seed);
+        for index in (1..self.nodes.len()).rev() {
+            let other = random.next_below(index + 1);
+            let position =
+                std::mem::replace(&mut self.nodes[index].position, Point { x: 0.0, y: 0.0 });
+            self.nodes[index].position =
+                std::mem::replace(&mut self.nodes[other].position, position);
+        }
+        for node in self.nodes.iter_mut() {
+            node.position.x += random.next_below(100) as f32 / 100.0;
+            node.position.y += random.next_below(100) as f32 / 100.0;
+        }
+    }
+
+    /// Pins nodes in place and keeps the other hints after every step of the
+    /// simulation. Nodes are given by their order of insertion.
+    pub fn set_hints(&mut self, hints: Vec<Hint>) {
+        for hint in &hints {
+            if let Hint::Pin(node_index, x, y) = hint {
+                let node = &mut self.nodes[*node_index];
+                node.position = Point {
+                    x: *x as f32,
+                    y: *y as f32,
+                };
+                node.is_pinned = true;
+            }
+        }
+        // Free nodes start right of the pinned ones, so none of them sits on
+        // top of a pinned node, and forces pull them into free space.
+        let pinned_right = self
+            .nodes
+            .iter()
+            .filter(|node| node.is_pinned)
+            .map(|node| node.position.x + PIN_SPACING as f32)
+            .reduce(f32::max);
+        if let Some(pinned_right) = pinned_right {
+            for node in self.nodes.iter_mut().filter(|node| !node.is_pinned) {
+                node.position.x += pinned_right;
+            }
+        }
+        self.hints = hints;
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.nodes.iter().any(|node| node.is_pinned)
+    }
+
+    /// Moves free nodes until aligned nodes share a coordinate and ordered
+    /// nodes are at least `PIN_SPACING` apart. With pins, free nodes also stay
+    /// right of and below the origin.
+    fn apply_hints(&mut self) {
+        let nodes = &mut self.nodes;
+        for hint in &self.hints {
+            match hint {
+                Hint::Pin(..) => {}
+                Hint::Same(axis, node_indices) => {
+                    let pinned = node_indices
+                        .iter()
+                        .find(|node_index| nodes[**node_index].is_pinned);
+                    let target = match pinned {
+                        Some(node_index) => {
+                            *get_coordinate(&mut nodes[*node_index].position, *axis)
+                        }
+                        None => {
+                            node_indices
+                                .iter()
+                                .map(|node_index| {
+                                    *get_coordinate(&mut nodes[*node_index].position, *axis)
+                                })
+                                .sum::<f32>()
+                                / node_indices.len() as f32
+                        }
+                    };
+                    for node_index in node_indices {
+                        let node = &mut nodes[*node_index];
+                        if !node.is_pinned {
+                            *get_coordinate(&mut node.position, *axis) = target;
+                        }
+                    }
+                }
+                Hint::Order(axis, before_index, after_index) => {
+                    let before = *get_coordinate(&mut nodes[*before_index].position, *axis);
+                    let after = *get_coordinate(&mut nodes[*after_index].position, *axis);
+                    let missing = PIN_SPACING as f32 - (after - before);
+                    if missing <= 0.0 {
+                        continue;
+                    }
+                    let (before_share, after_share) = match (
+                        nodes[*before_index].is_pinned,
+                        nodes[*after_index].is_pinned,
+                    ) {
+                        (true, true) => (0.0, 0.0),
+                        (true, false) => (0.0, missing),
+                        (false, true) => (missing, 0.0),
+                        (false, false) => (missing / 2.0, missing / 2.0),
+                    };
+                    *get_coordinate(&mut nodes[*before_index].position, *axis) -= before_share;
+                    *get_coordinate(&mut nodes[*after_index].position, *axis) += after_share;
+                }
+            }
+        }
+        if self.is_pinned() {
+            for node in self.nodes.iter_mut() {
+                node.position.x = node.position.x.max(0.0);
+                node.position.y = node.position.y.max(0.0);
+            }
+        }
+    }
+
+    fn get_adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adjacency = vec![Vec::new(); self.nodes.len()];
+        for edge in &self.edges {
+            adjacency[edge.from_index].push(edge.to_index);
+            adjacency[edge.to_index].push(edge.from_index);
+        }
+        adjacency
+    }
+
+    fn apply_force_of_attraction(
+        &self,
+        node_index: usize,
+        other_node_index: usize,
+        force: &mut Point,
+        attraction_strength: &f32,
+    ) {
+        let node = self.nodes.get(node_index).unwrap();
+        let other_node = self.nodes.get(other_node_index).unwrap();
+        let direction = Point {
+            x: other_node.position.x - node.position.x,
+            y: other_node.position.y - node.position.y,
+        };
+        let distance = calculate_distance(&node.position, &other_node.position);
+        let force_magnitude = attraction_strength * (distance / 5.0).log10();
+        force.x += force_magnitude * direction.x;
+        force.y += force_magnitude * direction.y;
+    }
+
+    fn apply_force_of_repulsion(
+        &self,
+        node_index: usize,
+        other_node_index: usize,
+        force: &mut Point,
+        repulsion_strength: &f32,
+    ) {
+        if node_index == other_node_index {
+            return;
+        }
+
+        let node = self.nodes.get(node_index).unwrap();
+        let other_node = self.nodes.get(other_node_index).unwrap();
+        let direction = Point {
+            x: node.position.x - other_node.position.x,
+            y: node.position.y - other_node.position.y,
+        };
+        let distance = calculate_distance(&node.position, &other_node.position);
+        let force_magnitude = repulsion_strength / distance.powi(2);
+        force.x += force_magnitude * direction.x;
+        force.y += force_magnitude * direction.y;
+    }
+
+    pub fn force_directed(&mut self, settings: &ForceDirectedLayout) -> Vec<NodeApproximation> {
+        self.simulate(settings);
+        self.get_transposed_nodes_approximation()
+    }
+
+    /// Runs until the average movement per node drops below the tolerance or
+    /// the iterations run out, returning the number of iterations used.
+    fn simulate(&mut self, settings: &ForceDirectedLayout) -> usize {
+        let adjacency = self.get_adjacency();
+        let theta = if self.nodes.len() > BARNES_HUT_THRESHOLD {
+            settings.theta
+        } else {
+            0.0
+        };
+        let mut temperature = settings.initial_temperature;
+        for iteration in 0..settings.iterations {
+            let movement = self.force_directed_iteration(
+                &adjacency,
+                settings.attraction_strength,
+                settings.repulsion_strength,
+                theta,
+                temperature,
+            );
+            self.apply_hints();
+            temperature *= settings.cooling_factor;
+            if movement / (self.nodes.len() as f32) < settings.tolerance {
+                return iteration + 1;
+            }
+        }
+        settings.iterations
+    }
+
+    fn get_forces(
+        &self,
+        adjacency: &[Vec<usize>],
+        attraction_strength: f32,
+        repulsion_strength: f32,
+        theta: f32,
+    ) -> Vec<Point> {
+        let positions = self
+            .nodes
+            .iter()
+            .map(|node| &node.position)
+            .collect::<Vec<_>>();
+        let quad_tree = if theta > 0.0 {
+            Some(quad_tree::QuadTree::new(&positions))
+        } else {
+            None
+        };
+
+        let mut forces = Vec::new();
+        for (node_index, neighbours) in adjacency.iter().enumerate() {
+            let mut force = Point { x: 0.0, y: 0.0 };
+            for other_node_index in neighbours {
+                self.apply_force_of_attraction(
+                    node_index,
+                    *other_node_index,
+                    &mut force,
+                    &attraction_strength,
+                );
+            }
+            match &quad_tree {
+                Some(quad_tree) => quad_tree.apply_force_of_repulsion(
+                    node_index,
+                    &positions,
+                    &mut force,
+                    repulsion_strength,
+                    theta,
+                ),
+                None => {
+                    for (other_node_index, _) in self.nodes.iter().enumerate() {
+                        self.apply_force_of_repulsion(
+                            node_index,
+                            other_node_index,
+                            &mut force,
+                            &repulsion_strength,
+                        );
+                    }
+                }
+            }
+            forces.push(force);
+        }
+        forces
+    }
+
+    fn force_directed_iteration(
+        &mut self,
+        adjacency: &[Vec<usize>],
+        attraction_strength: f32,
+        repulsion_strength: f32,
+        theta: f32,
+        temperature: f32,
+    ) -> f32 {
+        let forces = self.get_forces(adjacency, attraction_strength, repulsion_strength, theta);
+
+        let mut movement = 0.0;
+        let mut forces_iter = forces.iter();
+        self.nodes.iter_mut().for_each(|node| {
+            let force = forces_iter.next().unwrap();
+            if node.is_pinned {
+                return;
+            }
+            if force.x == 0.0 && force.y == 0.0 {
+                node.position.x += 0.1;
+                node.position.y += 0.1;
+            } else {
+                let length = (force.x.powi(2) + force.y.powi(2)).sqrt();
+                let scale = if length > temperature {
+                    temperature / length
+                } else {
+                    1.0
+                };
+                node.position.x += force.x * scale;
+                node.position.y += force.y * scale;
+                movement += length * scale;
+            }
+        });
+        movement
+    }
+
+    /// Shifts the layout to the origin, unless pins already placed it.
+    fn get_transposed_nodes_approximation(&self) -> Vec<NodeApproximation> {
+        if self.is_pinned() {
+            return self
+                .nodes
+                .iter()
+                .map(|inner_node| NodeApproximation {
+                    name: inner_node.name.to_owned(),
+                    position: PointApproximation {
+                        x: inner_node.position.x.round() as usize,
+                        y: inner_node.position.y.round() as usize,
+                    },
+                })
+                .collect();
+        }
+        let min_x = self
+            .nodes
+            .iter()
+            .map(|node| node.position.x)
+            .reduce(f32::min)
+            .unwrap()
+            .round() as i32;
+        let min_y = self
+            .nodes
+            .iter()
+            .map(|node| node.position.y)
+            .reduce(f32::min)
+            .unwrap()
+            .round() as i32;
+        let x_offset = 0 - min_x;
+        let y_offset = 0 - min_y;
+
+        self.nodes
+            .iter()
+            .map(|inner_node| NodeApproximation {
+                name: inner_node.name.to_owned(),
+                position: PointApproximation {
+                    x: (inner_node.position.x.round() as i32 + x_offset) as usize,
+                    y: (inner_node.position.y.round() as i32 + y_offset) as usize,
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inner_graph_placement_example_01() {
+        let mut under_test = InnerGraphPlacement::new();
+        let p1 = under_test.get_next_position();
+        let p2 = under_test.get_next_position();
+        let p3 = under_test.get_next_position();
+        let p4 = under_test.get_next_position();
+        assert_eq!(p1, Point { x: 0.0, y: 0.0 });
+        assert_eq!(p2, Point { x: 5.0, y: 0.0 });
+        assert_eq!(p3, Point { x: 10.0, y: 0.0 });
+        assert_eq!(p4, Point { x: 0.0, y: 5.0 });
+    }
+
+    fn get_nodes_approximation_picture(graph: &Graph) -> String {
+        let nodes_approximation = graph.get_transposed_nodes_approximation();
+        let max_x = nodes_approximation
+            .iter()
+            .map(|node| node.position.x)
+            .reduce(usize::max)
+            .unwrap();
+        let max_y = nodes_approximation
+            .iter()
+            .map(|node| node.position.y)
+            .reduce(usize::max)
+            .unwrap();
+
+        let mut builder = String::new();
+        for y in 0..=max_y {
+            for x in 0..=max_x {
+                let potential_char = nodes_approximation
+                    .iter()
+                    .find(|a| a.position.x == x && a.position.y == y);
+                match potential_char {
+                    None => builder.push(' '),
+                    Some(c) => builder.push(c.name.chars().next().unwrap()),
+                }
+            }
+            builder.push('\n');
+        }
+        builder
+    }
+
+    fn test(attraction_strength: f32, repulsion_strength: f32) -> String {
+        let mut graph = Graph::new();
+        graph.add_node("A");
+        graph.add_node("B");
+        graph.add_node("C");
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("C", "A");
+
+        graph.force_directed(&ForceDirectedLayout {
+            attraction_strength,
+            repulsion_strength,
+            ..ForceDirectedLayout::default()
+        });
+        get_nodes_approximation_picture(&graph)
+    }
+
+    fn test2(attraction_strength: f32, repulsion_strength: f32) -> String {
+        let mut graph = Graph::new();
+        graph.add_node("A");
+        graph.add_node("B");
+        graph.add_node("C");
+        graph.add_node("D");
+        graph.add_node("E");
+        graph.add_node("F");
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("C", "D");
+        graph.add_edge("D", "B");
+        graph.add_edge("D", "A");
+        graph.add_edge("A", "E");
+        graph.add_edge("E", "F");
+
+        graph.force_directed(&ForceDirectedLayout {
+            attraction_strength,
+            repulsion_strength,
+            ..ForceDirectedLayout::default()
+        });
+        get_nodes_approximation_picture(&graph)
+    }
+
+    fn test_with_extra_edges(attraction_strength: f32, repulsion_strength: f32) -> String {
+        let mut graph = Graph::new();
+        graph.add_node("A");
+        graph.add_node("A");
+        graph.add_node("B");
+        graph.add_node("C");
+        graph.add_edge("A", "B");
+        graph.add_edge("A", "B");
+        graph.add_edge("A", "A");
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("C", "A");
+
+        graph.force_directed(&ForceDirectedLayout {
+            attraction_strength,
+            repulsion_strength,
+            ..ForceDirectedLayout::default()
+        });
+        get_nodes_approximation_picture(&graph)
+    }
+
+    #[test]
+    fn get_example01() {
+        let result = test(1.0, 2.0);
+        let expected = "A     C
+       
+       
+       
+       
+   B   
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn get_example01_with_unnecessary_edges() {
+        let result = test_with_extra_edges(1.0, 2.0);
+        let expected = "A     C
+       
+       
+       
+       
+   B   
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn get_example02() {
+        let result = test2(1.0, 1.0);
+        let expected = "   C   
+       
+       
+       
+B      
+      D
+       
+       
+       
+       
+   A   
+       
+       
+       
+       
+       
+   E   
+       
+       
+       
+       
+       
+   F   
+";
+        assert_eq!(result, expected);
+    }
+
+    fn get_triangle() -> Graph {
+        let mut graph = Graph::new();
+        graph.add_node("A");
+        graph.add_node("B");
+        graph.add_node("C");
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("C", "A");
+        graph
+    }
+
+    #[test]
+    fn force_directed_keeps_pins_and_hints() {
+        let mut graph = get_triangle();
+        graph.add_node("D");
+        graph.add_edge("C", "D");
+        graph.set_hints(vec![
+            Hint::Pin(0, 10, 10),
+            Hint::Order(Axis::X, 1, 2),
+            Hint::Same(Axis::Y, vec![2, 3]),
+        ]);
+
+        let nodes = graph.force_directed(&ForceDirectedLayout::default());
+        assert_eq!((nodes[0].position.x, nodes[0].position.y), (10, 10));
+        assert!(nodes[1].position.x + PIN_SPACING <= nodes[2].position.x + 1);
+        assert_eq!(nodes[2].position.y, nodes[3].position.y);
+    }
+
+    #[test]
+    fn shuffle_is_reproducible() {
+        let get_positions = |seed| {
+            let mut graph = get_chain(10);
+            graph.shuffle(seed);
+            graph
+                .nodes
+                .iter()
+                .map(|node| (node.position.x, node.position.y))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(get_positions(7), get_positions(7));
+        assert_ne!(get_positions(7), get_positions(8));
+    }
+
+    #[test]
+    fn simulation_stops_once_converged() {
+        let settings = ForceDirectedLayout::default();
+        let iterations = get_triangle().simulate(&settings);
+        assert!(iterations < settings.iterations);
+    }
+
+    #[test]
+    fn simulation_movement_is_limited_by_temperature() {
+        let mut graph = get_triangle();
+        let adjacency = graph.get_adjacency();
+        let movement = graph.force_directed_iteration(&adjacency, 1.0, 100.0, 0.0, 0.5);
+        assert!(movement <= 1.5 + f32::EPSILON);
+    }
+
+    fn get_chain(count: usize) -> Graph {
+        let mut graph = Graph::new();
+        for index in 0..count {
+            graph.add_node(&index.to_string());
+        }
+        for index in 1..count {
+            graph.add_edge(&(index - 1).to_string(), &index.to_string());
+        }
+        graph
+    }
+
+    #[test]
+    fn graph_builds_large_inputs_quickly() {
+        let mut graph = Graph::new();
+        for index in 0..50_000 {
+            let from = (index % 5_000).to_string();
+            let to = ((index * 7 + 1) % 5_000).to_string();
+            graph.add_node(&from);
+            graph.add_node(&to);
+            graph.add_edge(&from, &to);
+            graph.add_edge(&to, &from);
+        }
+        assert_eq!(graph.nodes.len(), 5_000);
+        assert!(graph.edges.len() <= 50_000);
+        assert_eq!(graph.edges.len(), graph.edge_set.len());
+    }
+
+    #[test]
+    fn barnes_hut_forces_are_close_to_exact_forces() {
+        let graph = get_chain(200);
+        let adjacency = graph.get_adjacency();
+        let exact = graph.get_forces(&adjacency, 1.0, 1.0, 0.0);
+        let approximated = graph.get_forces(&adjacency, 1.0, 1.0, 0.5);
+        for (exact, approximated) in exact.iter().zip(approximated.iter()) {
+            let error = calculate_distance(exact, approximated);
+            let length = calculate_distance(exact, &Point { x: 0.0, y: 0.0 });
+            assert!(error <= 0.05 * length.max(1.0));
+        }
+    }
+
+    #[test]
+    fn barnes_hut_lays_out_large_graphs() {
+        let mut graph = get_chain(2000);
+        let nodes = graph.force_directed(&ForceDirectedLayout {
+            iterations: 10,
+            ..ForceDirectedLayout::default()
+        });
+        assert_eq!(nodes.len(), 2000);
+    }
+}
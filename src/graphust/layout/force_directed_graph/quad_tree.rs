@@ -0,0 +1,145 @@
+use super::{calculate_distance, Point};
+
+const MAX_DEPTH: usize = 32;
+
+enum Content {
+    Leaf(Vec<usize>),
+    Branch(Vec<QuadTree>),
+}
+
+/// Barnes–Hut quadtree. Every cell knows the number of nodes below it and
+/// their center of mass, so distant cells can repel as a single body.
+pub struct QuadTree {
+    min: Point,
+    size: f32,
+    mass: f32,
+    center: Point,
+    content: Content,
+}
+
+impl QuadTree {
+    pub fn new(positions: &[&Point]) -> Self {
+        let min_x = positions.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+        let min_y = positions.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+        let max_x = positions
+            .iter()
+            .map(|p| p.x)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let max_y = positions
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let size = (max_x - min_x).max(max_y - min_y).max(1.0);
+        Self::build(
+            positions,
+            (0..positions.len()).collect(),
+            Point { x: min_x, y: min_y },
+            size,
+            0,
+        )
+    }
+
+    fn build(
+        positions: &[&Point],
+        indices: Vec<usize>,
+        min: Point,
+        size: f32,
+        depth: usize,
+    ) -> Self {
+        let mass = indices.len() as f32;
+        let center = Point {
+            x: indices.iter().map(|index| positions[*index].x).sum::<f32>() / mass,
+            y: indices.iter().map(|index| positions[*index].y).sum::<f32>() / mass,
+        };
+        if indices.len() <= 1 || depth == MAX_DEPTH {
+            return Self {
+                min,
+                size,
+                mass,
+                center,
+                content: Content::Leaf(indices),
+            };
+        }
+
+        let half = size / 2.0;
+        let mut quadrants = vec![Vec::new(); 4];
+        for index in indices {
+            let right = positions[index].x >= min.x + half;
+            let bottom = positions[index].y >= min.y + half;
+            quadrants[usize::from(right) + 2 * usize::from(bottom)].push(index);
+        }
+        let children = quadrants
+            .into_iter()
+            .enumerate()
+            .filter(|(_, quadrant)| !quadrant.is_empty())
+            .map(|(quadrant_index, quadrant)| {
+                let child_min = Point {
+                    x: min.x + half * (quadrant_index % 2) as f32,
+                    y: min.y + half * (quadrant_index / 2) as f32,
+                };
+                Self::build(positions, quadrant, child_min, half, depth + 1)
+            })
+            .collect();
+        Self {
+            min,
+            size,
+            mass,
+            center,
+            content: Content::Branch(children),
+        }
+    }
+
+    fn contains(&self, point: &Point) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.min.x + self.size
+            && point.y >= self.min.y
+            && point.y <= self.min.y + self.size
+    }
+
+    pub fn apply_force_of_repulsion(
+        &self,
+        node_index: usize,
+        positions: &[&Point],
+        force: &mut Point,
+        repulsion_strength: f32,
+        theta: f32,
+    ) {
+        let position = positions[node_index];
+        match &self.content {
+            Content::Leaf(indices) => {
+                for other_index in indices.iter().filter(|index| **index != node_index) {
+                    add_repulsion(
+                        position,
+                        positions[*other_index],
+                        1.0,
+                        force,
+                        repulsion_strength,
+                    );
+                }
+            }
+            Content::Branch(children) => {
+                let distance = calculate_distance(position, &self.center);
+                if !self.contains(position) && self.size / distance < theta {
+                    add_repulsion(position, &self.center, self.mass, force, repulsion_strength);
+                } else {
+                    for child in children {
+                        child.apply_force_of_repulsion(
+                            node_index,
+                            positions,
+                            force,
+                            repulsion_strength,
+                            theta,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn add_repulsion(position: &Point, other: &Point, mass: f32, force: &mut Point, strength: f32) {
+    let distance = calculate_distance(position, other);
+    let force_magnitude = mass * strength / distance.powi(2);
+    force.x += force_magnitude * (position.x - other.x);
+    force.y += force_magnitude * (position.y - other.y);
+}
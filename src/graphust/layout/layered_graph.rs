@@ -0,0 +1,726 @@
+use crate::graphust::layout::{Axis, Hint, LayoutDirection, NodeApproximation, PointApproximation};
+use std::collections::{HashMap, HashSet};
+
+const VERTICAL_OFFSET: usize = 5;
+const HORIZONTAL_OFFSET: usize = 6;
+const ORDERING_PASSES: usize = 8;
+
+struct Node {
+    name: String,
+    is_dummy: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Edge {
+    from_index: usize,
+    to_index: usize,
+}
+
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    node_indices: HashMap<String, usize>,
+    edge_set: HashSet<(usize, usize)>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            node_indices: HashMap::new(),
+            edge_set: HashSet::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, node_label: &str) {
+        if self.node_indices.contains_key(node_label) {
+            return;
+        }
+
+        self.node_indices
+            .insert(node_label.to_string(), self.nodes.len());
+        self.nodes.push(Node {
+            name: node_label.to_string(),
+            is_dummy: false,
+        });
+    }
+
+    pub fn add_edge(&mut self, node_from_label: &str, node_to_label: &str) {
+        if node_from_label == node_to_label {
+            return;
+        }
+
+        let (Some(from_index), Some(to_index)) = (
+            self.node_indices.get(node_from_label),
+            self.node_indices.get(node_to_label),
+        ) else {
+            panic!("Could not find both nodes");
+        };
+        if !self.edge_set.insert((*from_index, *to_index)) {
+            return;
+        }
+
+        self.edges.push(Edge {
+            from_index: *from_index,
+            to_index: *to_index,
+        });
+    }
+
+    /// Hints along the flow constrain the ranks, hints across it the order
+    /// within a layer and the final coordinates. Pins override everything.
+    pub fn layered(
+        &mut self,
+        direction: LayoutDirection,
+        hints: &[Hint],
+    ) -> Vec<NodeApproximation> {
+        let main_axis = match direction {
+            LayoutDirection::LeftToRight | LayoutDirection::RightToLeft => Axis::X,
+            LayoutDirection::TopToBottom | LayoutDirection::BottomToTop => Axis::Y,
+        };
+        let is_reversed = matches!(
+            direction,
+            LayoutDirection::RightToLeft | LayoutDirection::BottomToTop
+        );
+        let mut precedences = Vec::new();
+        let mut same_ranks = Vec::new();
+        for hint in hints {
+            match hint {
+                Hint::Order(axis, before, after) if *axis == main_axis => {
+                    precedences.push(if is_reversed {
+                        (*after, *before)
+                    } else {
+                        (*before, *after)
+                    });
+                }
+                Hint::Same(axis, node_indices) if *axis == main_axis => {
+                    same_ranks.push(node_indices.clone());
+                }
+                _ => {}
+            }
+        }
+
+        self.break_cycles();
+        let ranks = self.assign_ranks(&precedences, &same_ranks);
+        let (layers, edges) = self.split_long_edges(ranks);
+        let mut layers = self.reduce_crossings(layers, &edges);
+        for hint in hints {
+            if let Hint::Order(axis, before, after) = hint {
+                if *axis != main_axis {
+                    keep_order(&mut layers, *before, *after);
+                }
+            }
+        }
+        let mut approximation = self.assign_coordinates(&layers, direction);
+        // Pins override everything, so aligned groups line up with their
+        // pinned member and pins are applied last.
+        let get_pin = |node_index: usize| {
+            hints.iter().find_map(|hint| match hint {
+                Hint::Pin(pinned, x, y) if *pinned == node_index => {
+                    Some(PointApproximation { x: *x, y: *y })
+                }
+                _ => None,
+            })
+        };
+        for hint in hints {
+            if let Hint::Same(axis, node_indices) = hint {
+                if *axis == main_axis {
+                    continue;
+                }
+                let pinned_target = node_indices.iter().find_map(|node_index| {
+                    let pin = get_pin(*node_index)?;
+                    Some(match axis {
+                        Axis::X => pin.x,
+                        Axis::Y => pin.y,
+                    })
+                });
+                let target = pinned_target.unwrap_or_else(|| {
+                    node_indices
+                        .iter()
+                        .map(|node_index| *get_coordinate(&mut approximation[*node_index], *axis))
+                        .max()
+                        .unwrap_or(0)
+                });
+                for node_index in node_indices {
+                    *get_coordinate(&mut approximation[*node_index], *axis) = target;
+                }
+            }
+        }
+        let mut placed = Vec::new();
+        for (node_index, node) in approximation.iter_mut().enumerate() {
+            if let Some(pin) = get_pin(node_index) {
+                placed.push((pin.x, pin.y));
+                node.position = pin;
+            }
+        }
+        if !placed.is_empty() {
+            // Free nodes step across the flow until they keep a slot away from
+            // every pinned node and every node placed before them.
+            let (cross_axis, cross_offset) = match main_axis {
+                Axis::X => (Axis::Y, VERTICAL_OFFSET),
+                Axis::Y => (Axis::X, HORIZONTAL_OFFSET),
+            };
+            for (node_index, node) in approximation.iter_mut().enumerate() {
+                if get_pin(node_index).is_some() {
+                    continue;
+                }
+                while placed.iter().any(|(x, y)| {
+                    x.abs_diff(node.position.x) < HORIZONTAL_OFFSET
+                        && y.abs_diff(node.position.y) < VERTICAL_OFFSET
+                }) {
+                    *get_coordinate(node, cross_axis) += cross_offset;
+                }
+                placed.push((node.position.x, node.position.y));
+            }
+        }
+        approximation
+    }
+
+    /// Reverses the edges which close a cycle during a depth-first search, so
+    /// the remaining graph is acyclic and can be ranked.
+    fn break_cycles(&mut self) {
+        let closes_cycle = get_cycle_edges(self.nodes.len(), &self.edges);
+        for (edge, is_reversed) in self.edges.iter_mut().zip(closes_cycle) {
+            if is_reversed {
+                *edge = Edge {
+                    from_index: edge.to_index,
+                    to_index: edge.from_index,
+                };
+            }
+        }
+    }
+
+    /// Longest path ranking: every node sits one layer below its lowest
+    /// predecessor, and the sources on the top layer 0. Precedences count as
+    /// edges. Nodes sharing a rank are ranked as one node, so edges between
+    /// them are dropped and cycles through a group are broken.
+    fn assign_ranks(
+        &self,
+        precedences: &[(usize, usize)],
+        same_ranks: &[Vec<usize>],
+    ) -> Vec<usize> {
+        let mut representatives = (0..self.nodes.len()).collect::<Vec<_>>();
+        let find = |representatives: &[usize], mut index: usize| {
+            while representatives[index] != index {
+                index = representatives[index];
+            }
+            index
+        };
+        for group in same_ranks {
+            for index in group.iter().skip(1) {
+                let (first, other) = (
+                    find(&representatives, group[0]),
+                    find(&representatives, *index),
+                );
+                representatives[other] = first;
+            }
+        }
+        let representatives = (0..self.nodes.len())
+            .map(|index| find(&representatives, index))
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .edges
+            .iter()
+            .copied()
+            .chain(precedences.iter().map(|(from_index, to_index)| Edge {
+                from_index: *from_index,
+                to_index: *to_index,
+            }))
+            .map(|edge| Edge {
+                from_index: representatives[edge.from_index],
+                to_index: representatives[edge.to_index],
+            })
+            .filter(|edge| edge.from_index != edge.to_index)
+            .collect::<Vec<_>>();
+        let closes_cycle = get_cycle_edges(self.nodes.len(), &edges);
+        let edges = edges
+            .into_iter()
+            .zip(closes_cycle)
+            .filter_map(|(edge, closes_cycle)| (!closes_cycle).then_some(edge))
+            .collect::<Vec<_>>();
+        let ranks = self.get_longest_path_ranks(&edges);
+        representatives
+            .iter()
+            .map(|representative| ranks[*representative])
+            .collect()
+    }
+
+    fn get_longest_path_ranks(&self, edges: &[Edge]) -> Vec<usize> {
+        let mut ranks = vec![0; self.nodes.len()];
+        let mut incoming = vec![0; self.nodes.len()];
+        let mut outgoing = vec![Vec::new(); self.nodes.len()];
+        for edge in edges {
+            incoming[edge.to_index] += 1;
+            outgoing[edge.from_index].push(edge.to_index);
+        }
+        let mut queue = (0..self.nodes.len())
+            .filter(|index| incoming[*index] == 0)
+            .collect::<Vec<_>>();
+        let mut position = 0;
+        while position < queue.len() {
+            let node_index = queue[position];
+            position += 1;
+            for to_index in &outgoing[node_index] {
+                ranks[*to_index] = ranks[*to_index].max(ranks[node_index] + 1);
+                incoming[*to_index] -= 1;
+                if incoming[*to_index] == 0 {
+                    queue.push(*to_index);
+                }
+            }
+        }
+        ranks
+    }
+
+    /// Replaces edges spanning several layers with chains of dummy nodes, so
+    /// every edge connects two neighbouring layers.
+    fn split_long_edges(&mut self, mut ranks: Vec<usize>) -> (Vec<Vec<usize>>, Vec<Edge>) {
+        let mut edges = Vec::new();
+        for edge in self.edges.clone() {
+            let mut from_index = edge.from_index;
+            for rank in (ranks[edge.from_index] + 1)..ranks[edge.to_index] {
+                self.nodes.push(Node {
+                    name: String::new(),
+                    is_dummy: true,
+                });
+                ranks.push(rank);
+                let dummy_index = self.nodes.len() - 1;
+                edges.push(Edge {
+                    from_index,
+                    to_index: dummy_index,
+                });
+                from_index = dummy_index;
+            }
+            edges.push(Edge {
+                from_index,
+                to_index: edge.to_index,
+            });
+        }
+
+        let layer_count = ranks.iter().max().map_or(0, |rank| rank + 1);
+        let mut layers = vec![Vec::new(); layer_count];
+        for (node_index, rank) in ranks.iter().enumerate() {
+            layers[*rank].push(node_index);
+        }
+        (layers, edges)
+    }
+
+    /// Barycenter heuristic, sweeping down and up through the layers and
+    /// keeping the ordering with the fewest crossings.
+    fn reduce_crossings(&self, mut layers: Vec<Vec<usize>>, edges: &[Edge]) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![Vec::new(); self.nodes.len()];
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        for edge in edges {
+            predecessors[edge.to_index].push(edge.from_index);
+            successors[edge.from_index].push(edge.to_index);
+        }
+        let mut positions = vec![None; self.nodes.len()];
+        let mut best_layers = layers.clone();
+        let mut best_crossings = count_crossings(&layers, edges);
+        for pass in 0..ORDERING_PASSES {
+            if pass % 2 == 0 {
+                for layer_index in 1..layers.len() {
+                    set_positions(&mut positions, &layers[layer_index - 1], true);
+                    order_by_barycenter(&mut layers[layer_index], &positions, &predecessors);
+                    set_positions(&mut positions, &layers[layer_index - 1], false);
+                }
+            } else {
+                for layer_index in (0..layers.len().saturating_sub(1)).rev() {
+                    set_positions(&mut positions, &layers[layer_index + 1], true);
+                    order_by_barycenter(&mut layers[layer_index], &positions, &successors);
+                    set_positions(&mut positions, &layers[layer_index + 1], false);
+                }
+            }
+            let crossings = count_crossings(&layers, edges);
+            if crossings < best_crossings {
+                best_crossings = crossings;
+                best_layers = layers.clone();
+            }
+        }
+        best_layers
+    }
+
+    fn assign_coordinates(
+        &self,
+        layers: &[Vec<usize>],
+        direction: LayoutDirection,
+    ) -> Vec<NodeApproximation> {
+        let is_horizontal = matches!(
+            direction,
+            LayoutDirection::LeftToRight | LayoutDirection::RightToLeft
+        );
+        let is_reversed = matches!(
+            direction,
+            LayoutDirection::RightToLeft | LayoutDirection::BottomToTop
+        );
+        let (rank_offset, position_offset) = if is_horizontal {
+            (HORIZONTAL_OFFSET, VERTICAL_OFFSET)
+        } else {
+            (VERTICAL_OFFSET, HORIZONTAL_OFFSET)
+        };
+        let widest_layer = layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+        let mut positions = vec![None; self.nodes.len()];
+        for (rank, layer) in layers.iter().enumerate() {
+            let main = if is_reversed {
+                (layers.len() - 1 - rank) * rank_offset
+            } else {
+                rank * rank_offset
+            };
+            let layer_offset = (widest_layer - layer.len()) * position_offset / 2;
+            for (position, node_index) in layer.iter().enumerate() {
+                let cross = layer_offset + position * position_offset;
+                positions[*node_index] = Some(if is_horizontal {
+                    (main, cross)
+                } else {
+                    (cross, main)
+                });
+            }
+        }
+        self.nodes
+            .iter()
+            .zip(positions)
+            .filter(|(node, _)| !node.is_dummy)
+            .map(|(node, position)| {
+                let (x, y) = position.unwrap();
+                NodeApproximation {
+                    name: node.name.to_owned(),
+                    position: PointApproximation { x, y },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Marks the edges which close a cycle during a depth-first search.
+fn get_cycle_edges(node_count: usize, edges: &[Edge]) -> Vec<bool> {
+    let mut outgoing = vec![Vec::new(); node_count];
+    for (edge_index, edge) in edges.iter().enumerate() {
+        outgoing[edge.from_index].push(edge_index);
+    }
+    let mut visited = vec![false; node_count];
+    let mut on_stack = vec![false; node_count];
+    let mut closes_cycle = vec![false; edges.len()];
+    for start in 0..node_count {
+        if visited[start] {
+            continue;
+        }
+        let mut stack = vec![(start, 0)];
+        visited[start] = true;
+        on_stack[start] = true;
+        while let Some((node_index, edge_position)) = stack.pop() {
+            match outgoing[node_index].get(edge_position) {
+                None => on_stack[node_index] = false,
+                Some(edge_index) => {
+                    stack.push((node_index, edge_position + 1));
+                    let to_index = edges[*edge_index].to_index;
+                    if on_stack[to_index] {
+                        closes_cycle[*edge_index] = true;
+                    } else if !visited[to_index] {
+                        visited[to_index] = true;
+                        on_stack[to_index] = true;
+                        stack.push((to_index, 0));
+                    }
+                }
+            }
+        }
+    }
+    closes_cycle
+}
+
+fn get_coordinate(node: &mut NodeApproximation, axis: Axis) -> &mut usize {
+    match axis {
+        Axis::X => &mut node.position.x,
+        Axis::Y => &mut node.position.y,
+    }
+}
+
+/// Swaps two nodes of the same layer if they are in the wrong order.
+fn keep_order(layers: &mut [Vec<usize>], before: usize, after: usize) {
+    for layer in layers {
+        let before_position = layer.iter().position(|node_index| *node_index == before);
+        let after_position = layer.iter().position(|node_index| *node_index == after);
+        if let (Some(before_position), Some(after_position)) = (before_position, after_position) {
+            if before_position > after_position {
+                layer.swap(before_position, after_position);
+            }
+        }
+    }
+}
+
+/// Sets or clears the positions of the nodes of a layer.
+fn set_positions(positions: &mut [Option<usize>], layer: &[usize], is_set: bool) {
+    for (position, node_index) in layer.iter().enumerate() {
+        positions[*node_index] = is_set.then_some(position);
+    }
+}
+
+fn order_by_barycenter(
+    layer: &mut [usize],
+    fixed_positions: &[Option<usize>],
+    neighbours: &[Vec<usize>],
+) {
+    let barycenters = layer
+        .iter()
+        .enumerate()
+        .map(|(position, node_index)| {
+            let neighbours = neighbours[*node_index]
+                .iter()
+                .filter_map(|neighbour| fixed_positions[*neighbour])
+                .collect::<Vec<_>>();
+            if neighbours.is_empty() {
+                position as f32
+            } else {
+                neighbours.iter().sum::<usize>() as f32 / neighbours.len() as f32
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut ordered = layer.iter().copied().zip(barycenters).collect::<Vec<_>>();
+    ordered.sort_by(|a, b| a.1.total_cmp(&b.1));
+    for (slot, (node_index, _)) in layer.iter_mut().zip(ordered) {
+        *slot = node_index;
+    }
+}
+
+fn count_crossings(layers: &[Vec<usize>], edges: &[Edge]) -> usize {
+    let node_count = layers.iter().map(|layer| layer.len()).sum::<usize>();
+    let mut positions = vec![0; node_count];
+    let mut ranks = vec![0; node_count];
+    for (rank, layer) in layers.iter().enumerate() {
+        for (position, node_index) in layer.iter().enumerate() {
+            positions[*node_index] = position;
+            ranks[*node_index] = rank;
+        }
+    }
+    let mut edge_positions = vec![Vec::new(); layers.len()];
+    for edge in edges {
+        edge_positions[ranks[edge.from_index]]
+            .push((positions[edge.from_index], positions[edge.to_index]));
+    }
+    // Two edges from a layer cross when their ends are in opposite orders, so
+    // with the edges sorted by their start the crossings are the inversions of
+    // the ends, counted with a Fenwick tree.
+    let widest_layer = layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+    let mut crossings = 0;
+    for mut layer_edges in edge_positions {
+        layer_edges.sort_unstable();
+        let mut tree = vec![0; widest_layer + 1];
+        for (count, (_, to_position)) in layer_edges.into_iter().enumerate() {
+            let mut index = to_position + 1;
+            let mut not_after = 0;
+            while index > 0 {
+                not_after += tree[index];
+                index &= index - 1;
+            }
+            crossings += count - not_after;
+            let mut index = to_position + 1;
+            while index <= widest_layer {
+                tree[index] += 1;
+                index += index & index.wrapping_neg();
+            }
+        }
+    }
+    crossings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_nodes_approximation_picture(nodes_approximation: &[NodeApproximation]) -> String {
+        let max_x = nodes_approximation
+            .iter()
+            .map(|node| node.position.x)
+            .reduce(usize::max)
+            .unwrap();
+        let max_y = nodes_approximation
+            .iter()
+            .map(|node| node.position.y)
+            .reduce(usize::max)
+            .unwrap();
+
+        let mut builder = String::new();
+        for y in 0..=max_y {
+            for x in 0..=max_x {
+                let potential_char = nodes_approximation
+                    .iter()
+                    .find(|a| a.position.x == x && a.position.y == y);
+                match potential_char {
+                    None => builder.push(' '),
+                    Some(c) => builder.push(c.name.chars().next().unwrap()),
+                }
+            }
+            builder.push('\n');
+        }
+        builder
+    }
+
+    #[test]
+    fn get_layered_diamond() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C", "D"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("A", "C");
+        graph.add_edge("B", "D");
+        graph.add_edge("C", "D");
+
+        let result =
+            get_nodes_approximation_picture(&graph.layered(LayoutDirection::TopToBottom, &[]));
+        let expected = "   A   
+       
+       
+       
+       
+B     C
+       
+       
+       
+       
+   D   
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn get_layered_with_cycle_and_long_edge() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("C", "A");
+        graph.add_edge("A", "C");
+
+        let result = graph.layered(LayoutDirection::TopToBottom, &[]);
+        let ranks = result
+            .iter()
+            .map(|node| (node.name.as_str(), node.position.y / VERTICAL_OFFSET))
+            .collect::<Vec<_>>();
+        assert_eq!(ranks, vec![("A", 0), ("B", 1), ("C", 2)]);
+    }
+
+    #[test]
+    fn get_layered_ranks_joined_groups_together() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C", "D"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("A", "D");
+        graph.add_edge("D", "B");
+
+        let result = graph.layered(
+            LayoutDirection::TopToBottom,
+            &[Hint::Same(Axis::Y, vec![0, 1, 2])],
+        );
+        let ranks = result
+            .iter()
+            .map(|node| (node.name.as_str(), node.position.y / VERTICAL_OFFSET))
+            .collect::<Vec<_>>();
+        assert_eq!(ranks, vec![("A", 0), ("B", 0), ("C", 0), ("D", 1)]);
+    }
+
+    #[test]
+    fn get_layered_left_to_right() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("A", "C");
+
+        let result =
+            get_nodes_approximation_picture(&graph.layered(LayoutDirection::LeftToRight, &[]));
+        let expected = "      B
+       
+A      
+       
+       
+      C
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn get_layered_with_hints() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C", "D"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("B", "C");
+        graph.add_edge("A", "D");
+
+        let result = graph.layered(
+            LayoutDirection::TopToBottom,
+            &[
+                Hint::Same(Axis::Y, vec![2, 3]),
+                Hint::Order(Axis::X, 3, 2),
+                Hint::Pin(0, 20, 0),
+            ],
+        );
+        let positions = result
+            .iter()
+            .map(|node| (node.position.x, node.position.y))
+            .collect::<Vec<_>>();
+        assert_eq!(positions[0], (20, 0));
+        assert_eq!(positions[2].1, positions[3].1);
+        assert!(positions[3].0 < positions[2].0);
+    }
+
+    #[test]
+    fn get_layered_aligns_groups_with_their_pinned_node() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+        graph.add_edge("A", "C");
+
+        let result = graph.layered(
+            LayoutDirection::TopToBottom,
+            &[Hint::Pin(1, 0, 0), Hint::Same(Axis::X, vec![1, 2])],
+        );
+        let positions = result
+            .iter()
+            .map(|node| (node.position.x, node.position.y))
+            .collect::<Vec<_>>();
+        assert_eq!(positions[1], (0, 0));
+        assert_eq!(positions[2].0, 0);
+    }
+
+    #[test]
+    fn get_layered_keeps_free_nodes_off_pinned_cells() {
+        let mut graph = Graph::new();
+        for node in ["A", "B"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "B");
+
+        let result = graph.layered(LayoutDirection::TopToBottom, &[Hint::Pin(1, 0, 0)]);
+        let positions = result
+            .iter()
+            .map(|node| (node.position.x, node.position.y))
+            .collect::<Vec<_>>();
+        assert_eq!(positions[1], (0, 0));
+        assert_ne!(positions[0], (0, 0));
+    }
+
+    #[test]
+    fn reduce_crossings_untangles_layers() {
+        let mut graph = Graph::new();
+        for node in ["A", "B", "C", "D"] {
+            graph.add_node(node);
+        }
+        graph.add_edge("A", "D");
+        graph.add_edge("B", "C");
+
+        let layers = vec![vec![0, 1], vec![2, 3]];
+        assert_eq!(1, count_crossings(&layers, &graph.edges));
+        let layers = graph.reduce_crossings(layers, &graph.edges);
+        assert_eq!(0, count_crossings(&layers, &graph.edges));
+    }
+}
@@ -0,0 +1,26 @@
+use crate::graphust::layout;
+
+pub struct Options {
+    pub layout: Box<dyn layout::LayoutEngine>,
+    /// Overrides the `direction` directive of the input when set.
+    pub direction: Option<layout::LayoutDirection>,
+    /// Positions of an earlier layout. Nodes found there stay in place and
+    /// new nodes are fitted around them.
+    pub previous_layout: Option<layout::LayoutCache>,
+    /// Largest drawing in characters. Larger drawings are spaced more tightly,
+    /// or turned on their side when no hints or earlier layout fix the
+    /// placement.
+    pub max_width: Option<usize>,
+    pub max_height: Option<usize>,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: Box::new(layout::ForceDirectedLayout::default()),
+            direction: None,
+            previous_layout: None,
+            max_width: None,
+            max_height: None,
+        }
+    }
+}
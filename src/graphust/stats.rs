@@ -0,0 +1,258 @@
+use crate::graphust::domain;
+
+const CROSSING_WEIGHT: usize = 10;
+const OVERLAP_WEIGHT: usize = 10;
+const EDGE_THROUGH_BOX_WEIGHT: usize = 10;
+const BEND_WEIGHT: usize = 2;
+/// Character cells of area that weigh as much as half a bend.
+const AREA_PER_POINT: usize = 40;
+
+/// Readability measures of a laid out map, in character cells. Arrows are
+/// counted the way they are drawn, so a step between two points that differ
+/// in both coordinates goes across first and then down or up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub crossings: usize,
+    pub bends: usize,
+    pub edge_length: usize,
+    pub overlaps: usize,
+    pub edges_through_boxes: usize,
+    pub width: usize,
+    pub height: usize,
+    pub area: usize,
+    /// Columns per row, not corrected for the shape of terminal cells.
+    pub aspect_ratio: f32,
+}
+
+impl Stats {
+    /// Crossings, overlapping boxes and edges through boxes weigh most, then
+    /// bends, then the area. Lower is better.
+    pub fn get_score(&self) -> usize {
+        self.crossings * CROSSING_WEIGHT
+            + self.overlaps * OVERLAP_WEIGHT
+            + self.edges_through_boxes * EDGE_THROUGH_BOX_WEIGHT
+            + self.bends * BEND_WEIGHT
+            + self.area / AREA_PER_POINT
+    }
+
+    pub fn get_report(&self) -> String {
+        format!(
+            "Crossings: {}\nBends: {}\nEdge length: {}\nOverlapping boxes: {}\nEdges through boxes: {}\nArea: {} x {} = {}\nAspect ratio: {:.2}\n",
+            self.crossings,
+            self.bends,
+            self.edge_length,
+            self.overlaps,
+            self.edges_through_boxes,
+            self.width,
+            self.height,
+            self.area,
+            self.aspect_ratio
+        )
+    }
+}
+
+/// A horizontal or vertical run from one point to another, both included.
+struct Segment {
+    from: (usize, usize),
+    to: (usize, usize),
+}
+impl Segment {
+    fn is_horizontal(&self) -> bool {
+        self.from.1 == self.to.1
+    }
+
+    fn get_length(&self) -> usize {
+        self.from.0.abs_diff(self.to.0) + self.from.1.abs_diff(self.to.1)
+    }
+
+    fn get_cells(&self) -> Vec<(usize, usize)> {
+        let (min_x, max_x) = (self.from.0.min(self.to.0), self.from.0.max(self.to.0));
+        let (min_y, max_y) = (self.from.1.min(self.to.1), self.from.1.max(self.to.1));
+        (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+            .collect()
+    }
+}
+
+fn get_segments(arrow: &domain::Arrow) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    for pair in arrow.points.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let corner = (to.x, from.y);
+        segments.push(Segment {
+            from: (from.x, from.y),
+            to: corner,
+        });
+        segments.push(Segment {
+            from: corner,
+            to: (to.x, to.y),
+        });
+    }
+    segments.retain(|segment| segment.get_length() > 0);
+    segments
+}
+
+fn get_bends(segments: &[Segment]) -> usize {
+    segments
+        .windows(2)
+        .filter(|pair| pair[0].is_horizontal() != pair[1].is_horizontal())
+        .count()
+}
+
+/// Whether a horizontal and a vertical segment cross away from their ends,
+/// where arrows leaving or entering the same box meet.
+fn segments_cross(horizontal: &Segment, vertical: &Segment) -> bool {
+    let x = vertical.from.0;
+    let y = horizontal.from.1;
+    let (left, right) = (
+        horizontal.from.0.min(horizontal.to.0),
+        horizontal.from.0.max(horizontal.to.0),
+    );
+    let (top, bottom) = (
+        vertical.from.1.min(vertical.to.1),
+        vertical.from.1.max(vertical.to.1),
+    );
+    left < x && x < right && top < y && y < bottom
+}
+
+fn get_crossings(arrows: &[Vec<Segment>]) -> usize {
+    let mut crossings = 0;
+    for (index, segments) in arrows.iter().enumerate() {
+        for other_segments in &arrows[index + 1..] {
+            for segment in segments {
+                crossings += other_segments
+                    .iter()
+                    .filter(
+                        |other| match (segment.is_horizontal(), other.is_horizontal()) {
+                            (true, false) => segments_cross(segment, other),
+                            (false, true) => segments_cross(other, segment),
+                            _ => false,
+                        },
+                    )
+                    .count();
+            }
+        }
+    }
+    crossings
+}
+
+pub fn get_stats(map: &domain::Map) -> Stats {
+    let boxes = map
+        .nodes
+        .iter()
+        .map(|(anchor, node)| (anchor, (node.get_width(), node.get_height())))
+        .collect::<Vec<_>>();
+    let overlaps = boxes
+        .iter()
+        .enumerate()
+        .map(|(index, (anchor, size))| {
+            boxes[index + 1..]
+                .iter()
+                .filter(|(other_anchor, other_size)| {
+                    domain::boxes_overlap(anchor, *size, other_anchor, *other_size, 0)
+                })
+                .count()
+        })
+        .sum();
+
+    let arrows = map.arrows.iter().map(get_segments).collect::<Vec<_>>();
+    let edges_through_boxes = arrows
+        .iter()
+        .filter(|segments| {
+            segments.iter().flat_map(Segment::get_cells).any(|(x, y)| {
+                let cell = domain::Point { x, y };
+                boxes
+                    .iter()
+                    .any(|(anchor, size)| domain::boxes_overlap(anchor, *size, &cell, (1, 1), 0))
+            })
+        })
+        .count();
+
+    let (width, height) = if map.nodes.is_empty() && map.arrows.is_empty() {
+        (0, 0)
+    } else {
+        map.get_size()
+    };
+    Stats {
+        crossings: get_crossings(&arrows),
+        bends: arrows.iter().map(|segments| get_bends(segments)).sum(),
+        edge_length: arrows.iter().flatten().map(Segment::get_length).sum(),
+        overlaps,
+        edges_through_boxes,
+        width,
+        height,
+        area: width * height,
+        aspect_ratio: if height == 0 {
+            0.0
+        } else {
+            width as f32 / height as f32
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphust::domain::{Arrow, Map, Node, Point};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn get_arrow(from: &str, to: &str, points: &[(usize, usize)]) -> Arrow {
+        Arrow {
+            points: points.iter().map(|(x, y)| Point { x: *x, y: *y }).collect(),
+            ..Arrow::new(
+                from,
+                to,
+                1,
+                Point { x: 0, y: 0 },
+                Point { x: 0, y: 0 },
+                Point { x: 0, y: 0 },
+            )
+        }
+    }
+
+    #[test]
+    fn get_stats_of_map() {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(Point { x: 0, y: 0 }, Node::new("A", 1));
+        nodes.insert(Point { x: 3, y: 1 }, Node::new("B", 2));
+        nodes.insert(Point { x: 20, y: 0 }, Node::new("C", 3));
+        let mut arrows = BTreeSet::new();
+        // Straight across, passing through B.
+        arrows.insert(get_arrow("A", "C", &[(5, 1), (19, 1)]));
+        // Down, across and down again, crossing the first arrow.
+        arrows.insert(get_arrow("C", "A", &[(12, 0), (12, 4), (2, 4), (2, 8)]));
+        let map = Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
+
+        let stats = get_stats(&map);
+        assert_eq!(
+            stats,
+            Stats {
+                crossings: 1,
+                bends: 2,
+                edge_length: 14 + 4 + 10 + 4,
+                overlaps: 1,
+                edges_through_boxes: 1,
+                width: 25,
+                height: 9,
+                area: 225,
+                aspect_ratio: 25.0 / 9.0,
+            }
+        );
+        assert!(stats.get_report().starts_with("Crossings: 1\nBends: 2\n"));
+        assert_eq!(stats.get_score(), 10 + 10 + 10 + 4 + 5);
+    }
+
+    #[test]
+    fn get_stats_of_empty_map() {
+        let map = Map {
+            nodes: BTreeMap::new(),
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        assert_eq!(get_stats(&map).area, 0);
+    }
+}
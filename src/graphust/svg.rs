@@ -0,0 +1,215 @@
+use crate::graphust::domain;
+
+const CELL_WIDTH: usize = 10;
+const CELL_HEIGHT: usize = 20;
+
+fn escape_text(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+fn get_cell_center(x: isize, y: isize) -> (isize, isize) {
+    (
+        x * CELL_WIDTH as isize + CELL_WIDTH as isize / 2,
+        y * CELL_HEIGHT as isize + CELL_HEIGHT as isize / 2,
+    )
+}
+
+fn get_step(from: &domain::Point, to: &domain::Point) -> (isize, isize) {
+    let x_diff = to.x as isize - from.x as isize;
+    let y_diff = to.y as isize - from.y as isize;
+    (x_diff.signum(), y_diff.signum())
+}
+
+fn add_node(anchor: &domain::Point, node: &domain::Node, builder: &mut String) {
+    let (x, y) = get_cell_center(anchor.x as isize, anchor.y as isize);
+    let width = (node.get_width() - 1) * CELL_WIDTH;
+    let height = (node.get_height() - 1) * CELL_HEIGHT;
+    builder.push_str(&format!(
+        "  <g class=\"node\" data-node=\"{}\">\n",
+        escape_text(&node.name)
+    ));
+    builder.push_str(&format!(
+        "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" />\n",
+        x, y, width, height
+    ));
+    builder.push_str(&format!(
+        "    <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+        x + width as isize / 2,
+        y + height as isize / 2,
+        escape_text(&node.name)
+    ));
+    builder.push_str("  </g>\n");
+}
+
+fn add_arrow(arrow: &domain::Arrow, builder: &mut String) {
+    // Arrow points sit one cell outside of the boxes, so both ends are
+    // stretched by a cell to touch the borders.
+    let start = arrow.get_start();
+    let end = arrow.get_end();
+    let first_step = arrow
+        .points
+        .iter()
+        .find(|point| *point != start)
+        .map_or((0, 0), |point| get_step(start, point));
+    let last_step = arrow
+        .points
+        .iter()
+        .rev()
+        .find(|point| *point != end)
+        .map_or((0, 0), |point| get_step(point, end));
+    let last_index = arrow.points.len() - 1;
+    let points = arrow
+        .points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| match index {
+            0 => get_cell_center(
+                point.x as isize - first_step.0,
+                point.y as isize - first_step.1,
+            ),
+            index if index == last_index => get_cell_center(
+                point.x as isize + last_step.0,
+                point.y as isize + last_step.1,
+            ),
+            _ => get_cell_center(point.x as isize, point.y as isize),
+        })
+        .collect::<Vec<_>>();
+    let points = points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ");
+    builder.push_str(&format!(
+        "  <polyline class=\"arrow\" data-from=\"{}\" data-to=\"{}\" points=\"{}\" marker-end=\"url(#arrow-head)\" />\n",
+        escape_text(&arrow.from),
+        escape_text(&arrow.to),
+        points
+    ));
+}
+
+pub struct SvgRenderer {
+    builder: String,
+}
+impl SvgRenderer {
+    pub fn new() -> Self {
+        Self {
+            builder: String::new(),
+        }
+    }
+}
+impl Default for SvgRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl domain::Renderer for SvgRenderer {
+    type Output = String;
+
+    fn begin(&mut self, width: usize, height: usize) {
+        self.builder.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            width * CELL_WIDTH,
+            height * CELL_HEIGHT
+        ));
+        self.builder.push_str(
+            "  <defs>
+    <marker id=\"arrow-head\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\">
+      <path d=\"M 0 0 L 10 5 L 0 10 z\" />
+    </marker>
+  </defs>
+  <style>
+    .node rect { fill: white; stroke: black; }
+    .arrow { fill: none; stroke: black; }
+    text { font-family: monospace; font-size: 14px; }
+  </style>
+",
+        );
+    }
+
+    fn add_arrow(&mut self, arrow: &domain::Arrow) {
+        add_arrow(arrow, &mut self.builder);
+    }
+
+    fn add_node(&mut self, anchor: &domain::Point, node: &domain::Node) {
+        add_node(anchor, node, &mut self.builder);
+    }
+
+    fn finish(mut self) -> String {
+        self.builder.push_str("</svg>\n");
+        self.builder
+    }
+}
+
+pub fn get_svg(map: &domain::Map) -> String {
+    map.render(SvgRenderer::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn svg_returns_with_arrow() {
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(domain::Point { x: 0, y: 0 }, domain::Node::new("A", 1));
+        nodes.insert(domain::Point { x: 10, y: 0 }, domain::Node::new("B", 1));
+        let mut arrows: BTreeSet<domain::Arrow> = BTreeSet::new();
+        arrows.insert(domain::Arrow::new(
+            "A",
+            "B",
+            1,
+            domain::Point { x: 5, y: 1 },
+            domain::Point { x: 6, y: 1 },
+            domain::Point { x: 9, y: 1 },
+        ));
+        let map = domain::Map {
+            nodes,
+            arrows,
+            diagnostics: Vec::new(),
+        };
+        let output = get_svg(&map);
+
+        assert!(output.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"150\" height=\"60\" viewBox=\"0 0 150 60\">\n"
+        ));
+        assert!(output.contains(
+            "  <polyline class=\"arrow\" data-from=\"A\" data-to=\"B\" points=\"45,30 65,30 105,30\" marker-end=\"url(#arrow-head)\" />\n"
+        ));
+        assert!(output.contains(
+            "  <g class=\"node\" data-node=\"A\">\n    <rect x=\"5\" y=\"10\" width=\"40\" height=\"40\" />\n    <text x=\"25\" y=\"30\" text-anchor=\"middle\" dominant-baseline=\"central\">A</text>\n  </g>\n"
+        ));
+        assert!(output.contains(
+            "  <g class=\"node\" data-node=\"B\">\n    <rect x=\"105\" y=\"10\" width=\"40\" height=\"40\" />\n    <text x=\"125\" y=\"30\" text-anchor=\"middle\" dominant-baseline=\"central\">B</text>\n  </g>\n"
+        ));
+        assert!(output.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn svg_escapes_text() {
+        let mut nodes: BTreeMap<domain::Point, domain::Node> = BTreeMap::new();
+        nodes.insert(
+            domain::Point { x: 0, y: 0 },
+            domain::Node::new("<A & \"B\">", 1),
+        );
+        let map = domain::Map {
+            nodes,
+            arrows: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+        let output = get_svg(&map);
+
+        assert!(output.contains(">&lt;A &amp; &quot;B&quot;&gt;</text>"));
+    }
+}
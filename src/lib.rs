@@ -0,0 +1,3 @@
+mod graphust;
+
+pub use graphust::*;
@@ -1,22 +1,167 @@
+use graphust::domain::AsciiRenderer;
+use graphust::html::HtmlRenderer;
+use graphust::svg::SvgRenderer;
+use graphust::{layout, stats, Options};
 use std::env;
-use std::io;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::process;
+use std::str::FromStr;
 
-mod graphust;
+const FORCE_DIRECTED_FLAGS: [&str; 10] = [
+    "--iterations",
+    "--attraction",
+    "--repulsion",
+    "--tolerance",
+    "--theta",
+    "--debug",
+    "--seed",
+    "--starts",
+    "--component-gap",
+    "--component-order",
+];
 
-fn main() {
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.unwrap_or_default();
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+/// The width of the terminal when stdout is one, from `COLUMNS` or else
+/// from `stty`.
+fn get_terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    if let Some(columns) = env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+    {
+        return Some(columns);
+    }
+    let terminal = fs::File::open("/dev/tty").ok()?;
+    let output = process::Command::new("stty")
+        .arg("size")
+        .stdin(terminal)
+        .output()
+        .ok()?;
+    String::from_utf8(output.stdout)
+        .ok()?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+fn get_output(args: Vec<String>) -> Result<String, String> {
     let mut buffer = "".to_string();
+    let mut format = "ascii".to_string();
+    let mut options = Options::default();
+    let mut layout_name = "force-directed".to_string();
+    let mut force_directed = layout::ForceDirectedLayout::default();
+    let mut input = None;
+    let mut cache_path = None;
+    let mut show_stats = false;
+    let mut max_width = None;
+    let mut force_directed_flag = None;
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if FORCE_DIRECTED_FLAGS.contains(&arg.as_str()) {
+            force_directed_flag = Some(arg.clone());
+        }
+        match arg.as_str() {
+            "--format" => format = args.next().unwrap_or_default(),
+            "--layout" => layout_name = args.next().unwrap_or_default(),
+            "--iterations" => force_directed.iterations = parse_value(&arg, args.next())?,
+            "--attraction" => force_directed.attraction_strength = parse_value(&arg, args.next())?,
+            "--repulsion" => force_directed.repulsion_strength = parse_value(&arg, args.next())?,
+            "--tolerance" => force_directed.tolerance = parse_value(&arg, args.next())?,
+            "--theta" => force_directed.theta = parse_value(&arg, args.next())?,
+            "--debug" => force_directed.debug = true,
+            "--seed" => force_directed.seed = parse_value(&arg, args.next())?,
+            "--starts" => force_directed.starts = parse_value(&arg, args.next())?,
+            "--cache" => cache_path = args.next(),
+            "--stats" => show_stats = true,
+            "--max-width" => max_width = Some(parse_value(&arg, args.next())?),
+            "--max-height" => {
+                options.max_height = Some(parse_value(&arg, args.next())?).filter(|max| *max > 0)
+            }
+            "--component-gap" => force_directed.component_gap = parse_value(&arg, args.next())?,
+            "--component-order" => {
+                force_directed.component_order =
+                    layout::ComponentOrder::from_name(&args.next().unwrap_or_default())?;
+            }
+            "--direction" => {
+                let direction =
+                    layout::LayoutDirection::from_name(&args.next().unwrap_or_default())?;
+                options.direction = Some(direction);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => input = Some(arg),
+        }
+    }
+    if let (Some(flag), true) = (&force_directed_flag, layout_name != "force-directed") {
+        return Err(format!(
+            "{} only applies to the force-directed layout, not {}",
+            flag, layout_name
+        ));
+    }
+    options.layout = match layout_name.as_str() {
+        "force-directed" => Box::new(force_directed),
+        _ => layout::get_engine(&layout_name)?,
+    };
+    if let Some(input) = input {
+        buffer = input;
+    } else {
         for line in io::stdin().lines() {
             buffer.push_str(&line.unwrap());
             buffer.push('\n');
         }
-    } else {
-        buffer = args[1].to_string();
     }
 
-    let output = graphust::get_graph(&buffer);
+    if !["ascii", "svg", "html"].contains(&format.as_str()) {
+        return Err(format!("Unknown output format: {}", format));
+    }
+    // Only drawings printed straight to a terminal are kept within its width.
+    options.max_width = max_width
+        .or_else(|| (format == "ascii").then(get_terminal_width).flatten())
+        .filter(|max| *max > 0);
+    if let Some(cache_path) = &cache_path {
+        match fs::read_to_string(cache_path) {
+            Ok(text) => options.previous_layout = Some(layout::LayoutCache::parse(&text)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(format!("Cannot read {}: {}", cache_path, error)),
+        }
+    }
+    let (map, layout_cache) = graphust::get_map_and_layout(&buffer, &options)?;
+    if let Some(cache_path) = &cache_path {
+        fs::write(cache_path, layout_cache.to_text())
+            .map_err(|error| format!("Cannot write {}: {}", cache_path, error))?;
+    }
+    for diagnostic in &map.diagnostics {
+        eprintln!("Warning: {}", diagnostic);
+    }
+    let report = show_stats.then(|| stats::get_stats(&map).get_report());
+    match format.as_str() {
+        "svg" | "html" => {
+            // Keep the document valid and report on stderr instead.
+            if let Some(report) = report {
+                eprint!("{}", report);
+            }
+            if format == "svg" {
+                Ok(map.render(SvgRenderer::new()))
+            } else {
+                Ok(map.render(HtmlRenderer::new()))
+            }
+        }
+        _ => Ok(map.render(AsciiRenderer::new()) + &report.unwrap_or_default()),
+    }
+}
+
+fn main() {
+    let output = get_output(env::args().skip(1).collect());
     if let Ok(output) = output {
         println!("{}", output);
     } else {